@@ -0,0 +1,68 @@
+#!/bin/sh
+# Captures the parts of /proc and /sys StatMonitor reads into a tarball,
+# which `replay` (or REPLAY_ROOT) then reads instead of the live system:
+#
+#   scripts/capture.sh capture.tar.gz
+#   mkdir capture && tar -xzf capture.tar.gz -C capture
+#   REPLAY_ROOT=capture stat_monitor
+#
+# Process command lines and /etc/passwd are included; pass --no-processes to
+# leave them out.
+#
+# Container mode reads the cgroup of the process itself, so the cgroup of the
+# running stat_monitor is recorded as /proc/self/cgroup; pass --pid <pid> to
+# record another process's, e.g. when several instances run.
+set -eu
+
+processes=true
+pid=
+while [ $# -gt 0 ]; do
+    case $1 in
+        --no-processes) processes=false; shift ;;
+        --pid) pid=$2; shift 2 ;;
+        *) break ;;
+    esac
+done
+if [ -z "$pid" ]; then
+    pid=$(pgrep -x -o stat_monitor || true)
+fi
+if [ -z "$pid" ]; then
+    echo "stat_monitor is not running, recording the cgroup of this shell" >&2
+    pid=$$
+fi
+out=${1:-statmonitor-capture.tar.gz}
+dir=$(mktemp -d)
+trap 'rm -rf "$dir"' EXIT
+
+# Copies a file, skipping those that vanish or cannot be read.
+copy() {
+    for file in "$@"; do
+        [ -f "$file" ] || continue
+        mkdir -p "$dir$(dirname "$file")"
+        cat "$file" > "$dir$file" 2>/dev/null || rm -f "$dir$file"
+    done
+}
+
+copy /proc/meminfo /proc/stat /proc/loadavg /proc/uptime /proc/diskstats
+mkdir -p "$dir/proc/self"
+cat "/proc/$pid/cgroup" > "$dir/proc/self/cgroup"
+copy /proc/pressure/cpu /proc/pressure/memory /proc/pressure/io
+copy /sys/class/net/*/statistics/*
+copy /sys/class/thermal/thermal_zone*/type /sys/class/thermal/thermal_zone*/temp
+copy /sys/class/hwmon/*/name /sys/class/hwmon/*/temp*_input /sys/class/hwmon/*/temp*_label
+
+if [ -f /sys/fs/cgroup/cgroup.controllers ]; then
+    find /sys/fs/cgroup -maxdepth 3 -type f \( -name cgroup.controllers -o -name 'cpu.*' \
+        -o -name 'memory.current' -o -name 'memory.max' -o -name memory.stat \
+        -o -name 'pids.*' -o -name io.stat \) | while read -r file; do copy "$file"; done
+fi
+
+if $processes; then
+    copy /etc/passwd
+    for pid in /proc/[0-9]*; do
+        copy "$pid/stat" "$pid/status" "$pid/cmdline"
+    done
+fi
+
+tar -czf "$out" -C "$dir" .
+echo "captured $out"
@@ -0,0 +1,656 @@
+use std::fmt;
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use hyper::{Body, Client, Method, Request, Uri};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use tokio::time::{timeout, Duration};
+
+use crate::error::ApiError;
+use crate::extract::ApiJson;
+use crate::{AppState, Shared};
+
+/// How long a webhook may take to answer before it is given up on.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Longest silence that can be created, in seconds: a year.
+const MAX_SILENCE: i64 = 365 * 86_400;
+
+/// Metrics that alert rules can be written against.
+pub const METRICS: &[&str] = &[
+    "cpu.user",
+    "cpu.nice",
+    "cpu.system",
+    "cpu.interrupt",
+    "cpu.idle",
+    "memory.used",
+    "memory.used_percent",
+    "memory.available",
+    "swap.used",
+    "swap.used_percent",
+    "load.one",
+    "load.five",
+    "load.fifteen",
+    "pressure.cpu.some.avg10",
+    "pressure.cpu.some.avg60",
+    "pressure.cpu.some.avg300",
+    "pressure.cpu.full.avg10",
+    "pressure.cpu.full.avg60",
+    "pressure.cpu.full.avg300",
+    "pressure.memory.some.avg10",
+    "pressure.memory.some.avg60",
+    "pressure.memory.some.avg300",
+    "pressure.memory.full.avg10",
+    "pressure.memory.full.avg60",
+    "pressure.memory.full.avg300",
+    "pressure.io.some.avg10",
+    "pressure.io.some.avg60",
+    "pressure.io.some.avg300",
+    "pressure.io.full.avg10",
+    "pressure.io.full.avg60",
+    "pressure.io.full.avg300",
+];
+
+/// The `[alerts]` section of the config.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AlertConfig {
+    /// URLs every state change is POSTed to. Only `http` is supported.
+    pub webhooks: Vec<String>,
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub name: String,
+    /// One of `METRICS`.
+    pub metric: String,
+    pub condition: Condition,
+    pub threshold: f64,
+    /// Seconds the condition must hold before the alert fires.
+    #[serde(rename = "for", default)]
+    pub for_secs: i64,
+    /// Value the metric must get back past before a firing alert resolves.
+    /// Defaults to `threshold`; set it below a `>` threshold, or above a `<`
+    /// one, so that a value hovering around the threshold does not flap.
+    pub clear: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub enum Condition {
+    #[serde(rename = ">")]
+    Above,
+    #[serde(rename = ">=")]
+    AtLeast,
+    #[serde(rename = "<")]
+    Below,
+    #[serde(rename = "<=")]
+    AtMost,
+}
+
+impl Condition {
+    fn holds(self, value: f64, threshold: f64) -> bool {
+        match self {
+            Condition::Above => value > threshold,
+            Condition::AtLeast => value >= threshold,
+            Condition::Below => value < threshold,
+            Condition::AtMost => value <= threshold,
+        }
+    }
+
+    fn is_upper_bound(self) -> bool {
+        matches!(self, Condition::Above | Condition::AtLeast)
+    }
+}
+
+impl Rule {
+    /// Checks the rule for mistakes that would keep it from ever working.
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if !METRICS.contains(&self.metric.as_str()) {
+            return Err("unknown metric");
+        }
+        if !self.threshold.is_finite() {
+            return Err("threshold must be a finite number");
+        }
+        if self.clear.is_some_and(|clear| !clear.is_finite()) {
+            return Err("clear must be a finite number");
+        }
+        if self.for_secs < 0 {
+            return Err("for must not be negative");
+        }
+        match self.clear {
+            Some(clear) if self.condition.is_upper_bound() && clear > self.threshold => {
+                Err("clear must not be above the threshold")
+            }
+            Some(clear) if !self.condition.is_upper_bound() && clear < self.threshold => {
+                Err("clear must not be below the threshold")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn resolves(&self, value: f64) -> bool {
+        !self.condition.holds(value, self.clear.unwrap_or(self.threshold))
+    }
+}
+
+/// Checks that notifications can be POSTed to `url`, a plain `http://` URL.
+pub fn validate_webhook(url: &str) -> Result<(), &'static str> {
+    let uri: Uri = url.parse().map_err(|_| "not a URL")?;
+    if uri.scheme_str() != Some("http") {
+        return Err("only http:// URLs are supported");
+    }
+    if uri.host().is_none() {
+        return Err("no host");
+    }
+    Ok(())
+}
+
+/// Looks up a rule metric in a snapshot; `None` if its collector did not
+/// report.
+pub fn value(state: &AppState, metric: &str) -> Option<f64> {
+    let percent = |used: u64, total: u64| {
+        if total == 0 {
+            0.0
+        } else {
+            used as f64 / total as f64 * 100.0
+        }
+    };
+    let cpu = state.cpu_usage.as_ref();
+    let memory = state.memory_usage.as_ref();
+    let swap = state.swap_usage.as_ref();
+    let load = state.load_average.as_ref();
+    if let Some(stall) = metric.strip_prefix("pressure.") {
+        return pressure(state, stall);
+    }
+    match metric {
+        "cpu.user" => cpu.map(|c| c.user as f64),
+        "cpu.nice" => cpu.map(|c| c.nice as f64),
+        "cpu.system" => cpu.map(|c| c.system as f64),
+        "cpu.interrupt" => cpu.map(|c| c.interrupt as f64),
+        "cpu.idle" => cpu.map(|c| c.idle as f64),
+        "memory.used" => memory.map(|m| m.used as f64),
+        "memory.used_percent" => memory.map(|m| percent(m.used, m.total)),
+        "memory.available" => memory.map(|m| m.available as f64),
+        "swap.used" => swap.map(|s| s.used as f64),
+        "swap.used_percent" => swap.map(|s| percent(s.used, s.total)),
+        "load.one" => load.map(|l| l.one as f64),
+        "load.five" => load.map(|l| l.five as f64),
+        "load.fifteen" => load.map(|l| l.fifteen as f64),
+        _ => None,
+    }
+}
+
+/// Looks up a `<resource>.<some|full>.<avg10|avg60|avg300>` stall share.
+fn pressure(state: &AppState, metric: &str) -> Option<f64> {
+    let mut parts = metric.split('.');
+    let pressure = state.pressure.as_ref()?;
+    let resource = match parts.next()? {
+        "cpu" => &pressure.cpu,
+        "memory" => &pressure.memory,
+        "io" => &pressure.io,
+        _ => return None,
+    };
+    let stall = match parts.next()? {
+        "some" => &resource.some,
+        "full" => resource.full.as_ref()?,
+        _ => return None,
+    };
+    let avg = match parts.next()? {
+        "avg10" => stall.avg10,
+        "avg60" => stall.avg60,
+        "avg300" => stall.avg300,
+        _ => return None,
+    };
+    Some(avg as f64)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Inactive,
+    /// The condition holds but has not held for long enough yet.
+    Pending,
+    Firing,
+    Resolved,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            Status::Inactive => "inactive",
+            Status::Pending => "pending",
+            Status::Firing => "firing",
+            Status::Resolved => "resolved",
+        };
+        f.write_str(status)
+    }
+}
+
+/// The state of one rule.
+#[derive(Debug, Clone, Serialize)]
+pub struct Alert {
+    pub name: String,
+    pub metric: String,
+    pub status: Status,
+    /// When the current status was entered.
+    pub since: i64,
+    /// The metric value that caused the last status change.
+    pub value: Option<f64>,
+    /// Whether a silence currently mutes this alert's notifications.
+    pub silenced: bool,
+}
+
+/// Mutes the notifications of one alert, or of all of them, for a while.
+/// Silenced alerts are still evaluated and listed.
+#[derive(Debug, Clone, Serialize)]
+pub struct Silence {
+    pub id: u64,
+    /// The rule name this silence applies to; `None` silences every alert.
+    pub alert: Option<String>,
+    pub starts_at: i64,
+    pub ends_at: i64,
+    pub comment: String,
+}
+
+impl Silence {
+    fn matches(&self, alert: &str, now: i64) -> bool {
+        self.alert.as_deref().is_none_or(|a| a == alert) && self.starts_at <= now && now < self.ends_at
+    }
+}
+
+/// Sent to the webhooks when an alert fires or resolves.
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    pub alert: String,
+    pub status: Status,
+    pub metric: String,
+    pub value: f64,
+    pub threshold: f64,
+    pub timestamp: i64,
+}
+
+/// Evaluates the rules against each snapshot and tracks their state.
+#[derive(Debug)]
+pub struct AlertEngine {
+    rules: Vec<Rule>,
+    alerts: Vec<Alert>,
+    silences: Vec<Silence>,
+    next_silence: u64,
+}
+
+impl AlertEngine {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        let alerts = rules
+            .iter()
+            .map(|rule| Alert {
+                name: rule.name.clone(),
+                metric: rule.metric.clone(),
+                status: Status::Inactive,
+                since: 0,
+                value: None,
+                silenced: false,
+            })
+            .collect();
+        AlertEngine {
+            rules,
+            alerts,
+            silences: Vec::new(),
+            next_silence: 1,
+        }
+    }
+
+    /// Alerts that are not inactive.
+    pub fn alerts(&self) -> impl Iterator<Item = &Alert> {
+        self.alerts.iter().filter(|a| a.status != Status::Inactive)
+    }
+
+    /// Silences that have not ended by `now`.
+    pub fn silences(&self, now: i64) -> impl Iterator<Item = &Silence> {
+        self.silences.iter().filter(move |s| s.ends_at > now)
+    }
+
+    /// Adds a silence and returns it.
+    pub fn silence(&mut self, alert: Option<String>, starts_at: i64, ends_at: i64, comment: String) -> Silence {
+        let silence = Silence {
+            id: self.next_silence,
+            alert,
+            starts_at,
+            ends_at,
+            comment,
+        };
+        self.next_silence += 1;
+        self.silences.push(silence.clone());
+        self.refresh_silenced(starts_at);
+        silence
+    }
+
+    /// Removes a silence, returning whether it existed.
+    pub fn unsilence(&mut self, id: u64, now: i64) -> bool {
+        let before = self.silences.len();
+        self.silences.retain(|s| s.id != id);
+        self.refresh_silenced(now);
+        self.silences.len() != before
+    }
+
+    fn refresh_silenced(&mut self, now: i64) {
+        self.silences.retain(|s| s.ends_at > now);
+        for alert in &mut self.alerts {
+            alert.silenced = self.silences.iter().any(|s| s.matches(&alert.name, now));
+        }
+    }
+
+    /// Advances every rule with a new snapshot and returns the alerts that
+    /// fired or resolved as a result, leaving out silenced ones.
+    pub fn evaluate(&mut self, state: &AppState) -> Vec<Notification> {
+        let now = state.last_updated;
+        self.refresh_silenced(now);
+        let mut notifications = Vec::new();
+        for (rule, alert) in self.rules.iter().zip(self.alerts.iter_mut()) {
+            let Some(value) = value(state, &rule.metric) else {
+                continue;
+            };
+            let holds = rule.condition.holds(value, rule.threshold);
+            let next = match alert.status {
+                Status::Inactive | Status::Resolved if holds && rule.for_secs == 0 => Status::Firing,
+                Status::Inactive | Status::Resolved if holds => Status::Pending,
+                Status::Pending if !holds => Status::Inactive,
+                Status::Pending if now - alert.since >= rule.for_secs => Status::Firing,
+                Status::Firing if rule.resolves(value) => Status::Resolved,
+                status => status,
+            };
+            if next == alert.status {
+                continue;
+            }
+            alert.status = next;
+            alert.since = now;
+            alert.value = Some(value);
+            if matches!(next, Status::Firing | Status::Resolved) && !alert.silenced {
+                notifications.push(Notification {
+                    alert: rule.name.clone(),
+                    status: next,
+                    metric: rule.metric.clone(),
+                    value,
+                    threshold: rule.threshold,
+                    timestamp: now,
+                });
+            }
+        }
+        notifications
+    }
+}
+
+/// Spawns the task evaluating the configured rules against every snapshot
+/// the collector publishes.
+pub fn spawn(shared: Shared) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let config = &shared.config.alerts;
+        let mut updates = shared.updates.clone();
+        while updates.changed().await.is_ok() {
+            let state = updates.borrow_and_update().clone();
+            let notifications = shared.alerts.write().await.evaluate(&state);
+            for notification in notifications {
+                log::info!("alert {} is {}", notification.alert, notification.status);
+                for url in &config.webhooks {
+                    // Delivered in the background so that a slow webhook
+                    // does not delay the next evaluation.
+                    tokio::spawn(notify(url.clone(), notification.clone()));
+                }
+            }
+        }
+    })
+}
+
+async fn notify(url: String, notification: Notification) {
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(&url)
+        .header("content-type", "application/json")
+        .body(Body::from(serde_json::to_vec(&notification).unwrap()));
+    let request = match request {
+        Ok(request) => request,
+        Err(e) => {
+            log::warn!("invalid webhook url {}: {}", url, e);
+            return;
+        }
+    };
+    match timeout(WEBHOOK_TIMEOUT, Client::new().request(request)).await {
+        Ok(Ok(response)) if response.status().is_success() => {}
+        Ok(Ok(response)) => log::warn!("webhook {} answered {}", url, response.status()),
+        Ok(Err(e)) => log::warn!("webhook {} failed: {}", url, e),
+        Err(_) => log::warn!("webhook {} timed out", url),
+    }
+}
+
+/// Lists the alerts that are pending, firing or resolved, and the silences
+/// that have not ended yet.
+pub async fn list(State(shared): State<Shared>) -> Json<serde_json::Value> {
+    let now = chrono::Utc::now().timestamp();
+    let engine = shared.alerts.read().await;
+    let alerts: Vec<&Alert> = engine.alerts().collect();
+    let silences: Vec<&Silence> = engine.silences(now).collect();
+    serde_json::json!({ "alerts": alerts, "silences": silences }).into()
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SilenceRequest {
+    /// Rule to silence; every alert if omitted.
+    alert: Option<String>,
+    /// Seconds from now the silence lasts.
+    duration: i64,
+    #[serde(default)]
+    comment: String,
+}
+
+/// Creates a silence from a `SilenceRequest`.
+pub async fn silence(
+    State(shared): State<Shared>,
+    ApiJson(request): ApiJson<SilenceRequest>,
+) -> Result<(StatusCode, Json<Silence>), ApiError> {
+    if !(1..=MAX_SILENCE).contains(&request.duration) {
+        return Err(ApiError::BadRequest("duration must be between 1 second and a year"));
+    }
+    let rules = &shared.config.alerts.rules;
+    if let Some(alert) = &request.alert {
+        if !rules.iter().any(|rule| &rule.name == alert) {
+            return Err(ApiError::BadRequest("no alert rule has this name"));
+        }
+    }
+    let now = chrono::Utc::now().timestamp();
+    let silence = shared
+        .alerts
+        .write()
+        .await
+        .silence(request.alert, now, now + request.duration, request.comment);
+    log::info!("silence {} created until {}", silence.id, silence.ends_at);
+    Ok((StatusCode::CREATED, Json(silence)))
+}
+
+/// Lifts a silence before it ends.
+pub async fn unsilence(State(shared): State<Shared>, Path(id): Path<u64>) -> Result<StatusCode, ApiError> {
+    let now = chrono::Utc::now().timestamp();
+    if shared.alerts.write().await.unsilence(id, now) {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::NotFound("no such silence"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::routing::post;
+    use axum::Router;
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::LoadAverage;
+
+    fn rule(for_secs: i64, clear: Option<f64>) -> Rule {
+        Rule {
+            name: "load".into(),
+            metric: "load.one".into(),
+            condition: Condition::Above,
+            threshold: 1.0,
+            for_secs,
+            clear,
+        }
+    }
+
+    fn state(last_updated: i64, load: f32) -> AppState {
+        AppState {
+            load_average: Some(LoadAverage {
+                one: load,
+                ..LoadAverage::default()
+            }),
+            last_updated,
+            ..AppState::default()
+        }
+    }
+
+    fn status(engine: &AlertEngine) -> Status {
+        engine.alerts.iter().find(|a| a.name == "load").unwrap().status
+    }
+
+    fn statuses(notifications: &[Notification]) -> Vec<Status> {
+        notifications.iter().map(|n| n.status).collect()
+    }
+
+    #[test]
+    fn fires_once_held_for_long_enough() {
+        let mut engine = AlertEngine::new(vec![rule(60, None)]);
+        assert!(engine.evaluate(&state(1000, 2.0)).is_empty());
+        assert_eq!(status(&engine), Status::Pending);
+        assert!(engine.evaluate(&state(1030, 2.0)).is_empty());
+        assert_eq!(status(&engine), Status::Pending);
+
+        let notifications = engine.evaluate(&state(1060, 3.0));
+        assert_eq!(statuses(&notifications), [Status::Firing]);
+        assert_eq!((notifications[0].value, notifications[0].timestamp), (3.0, 1060));
+        assert_eq!(engine.alerts().next().unwrap().since, 1060);
+    }
+
+    #[test]
+    fn pending_ends_when_condition_stops_holding() {
+        let mut engine = AlertEngine::new(vec![rule(60, None)]);
+        engine.evaluate(&state(1000, 2.0));
+        assert!(engine.evaluate(&state(1030, 0.5)).is_empty());
+        assert_eq!(status(&engine), Status::Inactive);
+        // The delay starts over.
+        engine.evaluate(&state(1040, 2.0));
+        assert!(engine.evaluate(&state(1070, 2.0)).is_empty());
+        assert_eq!(statuses(&engine.evaluate(&state(1100, 2.0))), [Status::Firing]);
+    }
+
+    #[test]
+    fn resolves_past_clear() {
+        let mut engine = AlertEngine::new(vec![rule(0, Some(0.5))]);
+        assert_eq!(statuses(&engine.evaluate(&state(1000, 2.0))), [Status::Firing]);
+        // Below the threshold but not past `clear`.
+        assert!(engine.evaluate(&state(1010, 0.8)).is_empty());
+        assert_eq!(status(&engine), Status::Firing);
+        assert_eq!(statuses(&engine.evaluate(&state(1020, 0.4))), [Status::Resolved]);
+        assert!(engine.evaluate(&state(1030, 0.4)).is_empty());
+    }
+
+    #[test]
+    fn fires_again_after_resolving() {
+        let mut engine = AlertEngine::new(vec![rule(0, None)]);
+        engine.evaluate(&state(1000, 2.0));
+        engine.evaluate(&state(1010, 0.5));
+        assert_eq!(status(&engine), Status::Resolved);
+        assert_eq!(statuses(&engine.evaluate(&state(1020, 2.0))), [Status::Firing]);
+    }
+
+    #[test]
+    fn missing_metric_keeps_state() {
+        let mut engine = AlertEngine::new(vec![rule(0, None)]);
+        engine.evaluate(&state(1000, 2.0));
+        let mut state = state(1010, 0.0);
+        state.load_average = None;
+        assert!(engine.evaluate(&state).is_empty());
+        assert_eq!(status(&engine), Status::Firing);
+    }
+
+    #[test]
+    fn silence_mutes_notifications() {
+        let mut engine = AlertEngine::new(vec![rule(0, None)]);
+        let silence = engine.silence(Some("load".into()), 1000, 1100, String::new());
+        assert!(engine.evaluate(&state(1010, 2.0)).is_empty());
+        let alert = engine.alerts().next().unwrap();
+        assert_eq!((alert.status, alert.silenced), (Status::Firing, true));
+
+        assert!(engine.unsilence(silence.id, 1020));
+        assert!(!engine.unsilence(silence.id, 1020));
+        assert_eq!(statuses(&engine.evaluate(&state(1030, 0.5))), [Status::Resolved]);
+    }
+
+    #[test]
+    fn silence_ends() {
+        let mut engine = AlertEngine::new(vec![rule(0, None)]);
+        engine.silence(None, 1000, 1100, String::new());
+        assert!(engine.evaluate(&state(1010, 2.0)).is_empty());
+        assert_eq!(engine.silences(1050).count(), 1);
+        assert_eq!(engine.silences(1100).count(), 0);
+        assert_eq!(statuses(&engine.evaluate(&state(1100, 0.5))), [Status::Resolved]);
+    }
+
+    #[test]
+    fn validates_rules() {
+        assert_eq!(rule(0, Some(0.5)).validate(), Ok(()));
+        let mut unknown = rule(0, None);
+        unknown.metric = "load.two".into();
+        assert_eq!(unknown.validate(), Err("unknown metric"));
+        assert_eq!(rule(-1, None).validate(), Err("for must not be negative"));
+        assert_eq!(rule(0, Some(2.0)).validate(), Err("clear must not be above the threshold"));
+
+        let mut nan = rule(0, None);
+        nan.threshold = f64::NAN;
+        assert_eq!(nan.validate(), Err("threshold must be a finite number"));
+        assert_eq!(rule(0, Some(f64::NAN)).validate(), Err("clear must be a finite number"));
+
+        let mut below = rule(0, Some(0.5));
+        below.condition = Condition::Below;
+        assert_eq!(below.validate(), Err("clear must not be below the threshold"));
+    }
+
+    #[test]
+    fn validates_webhooks() {
+        assert_eq!(validate_webhook("http://127.0.0.1:9000/hook"), Ok(()));
+        assert_eq!(validate_webhook("https://hooks.example.com/x"), Err("only http:// URLs are supported"));
+        assert_eq!(validate_webhook("not a url"), Err("not a URL"));
+        assert_eq!(validate_webhook("/hook"), Err("only http:// URLs are supported"));
+    }
+
+    #[tokio::test]
+    async fn posts_notification_to_webhook() {
+        let (sender, mut received) = mpsc::unbounded_channel();
+        let app = Router::new().route(
+            "/hook",
+            post(move |Json(body): Json<serde_json::Value>| async move {
+                sender.send(body).unwrap();
+            }),
+        );
+        let server = axum::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(app.into_make_service());
+        let url = format!("http://{}/hook", server.local_addr());
+        tokio::spawn(server);
+
+        let mut engine = AlertEngine::new(vec![rule(0, None)]);
+        let notification = engine.evaluate(&state(1000, 2.0)).remove(0);
+        notify(url, notification).await;
+
+        let body = received.recv().await.unwrap();
+        let expected = serde_json::json!({
+            "alert": "load",
+            "status": "firing",
+            "metric": "load.one",
+            "value": 2.0,
+            "threshold": 1.0,
+            "timestamp": 1000,
+        });
+        assert_eq!(body, expected);
+    }
+}
@@ -0,0 +1,277 @@
+pub mod builtin;
+pub mod cgroups;
+pub mod container;
+pub mod diskio;
+pub mod disks;
+pub mod network;
+pub mod platform;
+pub mod pressure;
+pub mod processes;
+pub mod thermal;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::{io, mem};
+
+use serde::Serialize;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::{interval, sleep, Duration, Instant, MissedTickBehavior};
+
+use platform::Platform;
+
+use crate::config::{Collectors, Config, ConfigError};
+use crate::error::CollectorError;
+use crate::{AppState, Shared};
+
+/// Window over which CPU load is measured for each sample.
+const CPU_WINDOW: Duration = Duration::from_secs(1);
+
+/// A source of metrics sampled by the collector task.
+///
+/// Each sample, every enabled collector is first `start`ed, then, after
+/// `CPU_WINDOW` if any of them asked for it, `collect`ed into the snapshot.
+pub trait Collector: Send {
+    /// Name of the collector in `[collectors]`, `AppState::collectors` and
+    /// errors.
+    fn name(&self) -> &'static str;
+
+    /// What the collector adds to the snapshot.
+    fn schema(&self) -> Schema;
+
+    /// Whether the collector runs if `[collectors]` does not mention it.
+    fn enabled_by_default(&self) -> bool {
+        true
+    }
+
+    /// Begins a measurement over the sampling window, such as CPU usage.
+    /// Returns whether the collector needs the window.
+    fn start(&mut self, _ctx: &Context) -> bool {
+        false
+    }
+
+    /// Reads the metrics into `state`. On failure, the collector is listed in
+    /// `AppState::errors` and the other collectors still report.
+    fn collect(&mut self, ctx: &Context, state: &mut AppState) -> io::Result<()>;
+}
+
+/// Describes the output of a collector, as listed by `/collectors`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Schema {
+    /// Keys of the snapshot JSON the collector fills in.
+    pub keys: &'static [&'static str],
+    pub description: &'static str,
+}
+
+/// A collector as listed by `/collectors`.
+#[derive(Debug, Clone, Serialize)]
+pub struct CollectorInfo {
+    pub name: &'static str,
+    #[serde(flatten)]
+    pub schema: Schema,
+}
+
+/// What collectors get to look at while sampling.
+pub struct Context<'a> {
+    pub platform: &'a dyn Platform,
+    pub config: &'a Config,
+    /// Our own cgroup in container mode, or the error that kept it from
+    /// being found.
+    container: Option<io::Result<PathBuf>>,
+}
+
+impl Context<'_> {
+    /// Our own cgroup in container mode, `None` otherwise. Fails if it could
+    /// not be found, which then fails the collectors that need it.
+    pub fn container(&self) -> Option<io::Result<&Path>> {
+        let dir = self.container.as_ref()?;
+        Some(dir.as_deref().map_err(|e| io::Error::new(e.kind(), e.to_string())))
+    }
+}
+
+/// The collectors the sampler iterates, in the order they are collected.
+#[derive(Default)]
+pub struct Registry {
+    collectors: Vec<Box<dyn Collector>>,
+}
+
+impl Registry {
+    /// Every built-in collector.
+    pub fn builtin() -> Self {
+        let mut registry = Registry::default();
+        registry.register(builtin::Memory);
+        registry.register(builtin::Swap);
+        registry.register(builtin::Disks);
+        registry.register(builtin::DiskIo::default());
+        registry.register(builtin::Network::default());
+        registry.register(builtin::Load);
+        registry.register(builtin::Uptime);
+        registry.register(builtin::Pressure);
+        registry.register(builtin::Cgroups::default());
+        registry.register(builtin::Thermal);
+        registry.register(builtin::Cpu::default());
+        registry.register(builtin::Processes::default());
+        registry
+    }
+
+    /// Adds a collector, replacing any with the same name.
+    pub fn register(&mut self, collector: impl Collector + 'static) {
+        self.collectors.retain(|c| c.name() != collector.name());
+        self.collectors.push(Box::new(collector));
+    }
+
+    /// Keeps only the collectors enabled in `[collectors]`. Fails if it
+    /// names a collector that does not exist.
+    pub fn configure(mut self, enabled: &Collectors) -> Result<Self, ConfigError> {
+        if let Some(name) = enabled.names().find(|name| !self.collectors.iter().any(|c| c.name() == *name)) {
+            return Err(ConfigError::UnknownCollector(name.to_string()));
+        }
+        self.collectors.retain(|c| enabled.enabled(c.name(), c.enabled_by_default()));
+        Ok(self)
+    }
+
+    pub fn info(&self) -> Vec<CollectorInfo> {
+        self.collectors
+            .iter()
+            .map(|c| CollectorInfo {
+                name: c.name(),
+                schema: c.schema(),
+            })
+            .collect()
+    }
+}
+
+/// Spawns the collector task. It takes a sample at the configured interval,
+/// swaps the result into the shared state, appends it to the history and
+/// publishes it to `publisher`, so handlers only ever take the locks to read.
+pub fn spawn(shared: Shared, mut sampler: Sampler, publisher: watch::Sender<Arc<AppState>>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = interval(shared.config.sample_interval());
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            let snapshot = sampler.sample().await;
+            publish(&shared, &publisher, snapshot).await;
+        }
+    })
+}
+
+/// Makes `snapshot` the latest one served and appends it to the history.
+pub async fn publish(shared: &Shared, publisher: &watch::Sender<Arc<AppState>>, snapshot: AppState) {
+    shared.history.write().await.push(&snapshot);
+    // A watch channel never blocks on slow subscribers; they simply skip to
+    // the latest snapshot.
+    publisher.send_replace(Arc::new(snapshot.clone()));
+    *shared.state.write().await = snapshot;
+}
+
+/// Takes snapshots of the collectors of a registry, which keep what they
+/// need to remember between two samples, such as counters to derive rates
+/// from.
+pub struct Sampler {
+    config: Arc<Config>,
+    registry: Registry,
+    platform: Box<dyn Platform>,
+}
+
+impl Sampler {
+    /// A sampler of the machine StatMonitor runs on, or of the capture
+    /// configured with `replay`.
+    pub fn new(config: Arc<Config>, registry: Registry) -> Self {
+        match config.replay.clone() {
+            Some(root) => Sampler::with_platform(config, registry, platform::Replay::new(root)),
+            None => Sampler::with_platform(config, registry, platform::Live::default()),
+        }
+    }
+
+    pub fn with_platform(config: Arc<Config>, registry: Registry, platform: impl Platform + 'static) -> Self {
+        Sampler {
+            config,
+            registry,
+            platform: Box::new(platform),
+        }
+    }
+
+    /// Takes a single snapshot of the collectors. A failing collector is
+    /// listed in `AppState::errors` and leaves its metrics empty without
+    /// affecting the others.
+    ///
+    /// This waits for `CPU_WINDOW` while CPU usage is measured and must
+    /// therefore never be awaited while holding the state lock.
+    pub async fn sample(&mut self) -> AppState {
+        let config = &self.config;
+        let ctx = Context {
+            platform: &*self.platform,
+            config,
+            container: config.container.then(|| container::detect(&*self.platform, &config.cgroups.root)),
+        };
+        let mut state = AppState::default();
+
+        let mut window = false;
+        for collector in &mut self.registry.collectors {
+            window |= collector.start(&ctx);
+        }
+        if window {
+            sleep(CPU_WINDOW).await;
+        }
+        for collector in &mut self.registry.collectors {
+            let name = collector.name();
+            state.collectors.push(name);
+            if let Err(e) = collector.collect(&ctx, &mut state) {
+                log::debug!("{} collector failed: {}", name, e);
+                state.errors.push(CollectorError::new(name, &e));
+            }
+        }
+
+        state.last_updated = chrono::Utc::now().timestamp();
+        state
+    }
+}
+
+/// Remembers counters by name from one sample to the next, so that
+/// collectors can derive per-second rates from them.
+///
+/// Each sample `start`s, `track`s every current counter and `finish`es;
+/// counters not tracked in a sample, such as those of a device that went
+/// away, are forgotten. A sample that fails before `finish` is discarded.
+#[derive(Debug)]
+pub struct RateTracker<T> {
+    previous: HashMap<String, T>,
+    current: HashMap<String, T>,
+    taken_at: Option<Instant>,
+    started_at: Option<Instant>,
+}
+
+impl<T> Default for RateTracker<T> {
+    fn default() -> Self {
+        RateTracker {
+            previous: HashMap::new(),
+            current: HashMap::new(),
+            taken_at: None,
+            started_at: None,
+        }
+    }
+}
+
+impl<T: Copy> RateTracker<T> {
+    pub fn start(&mut self) {
+        self.current.clear();
+        self.started_at = Some(Instant::now());
+    }
+
+    /// Records the counters of `name` in this sample and returns them as of
+    /// the previous sample, along with the seconds elapsed since; `None` the
+    /// first time `name` is seen.
+    pub fn track(&mut self, name: &str, counters: T) -> Option<(T, f64)> {
+        self.current.insert(name.to_string(), counters);
+        let elapsed = self.started_at?.duration_since(self.taken_at?).as_secs_f64();
+        let previous = *self.previous.get(name)?;
+        (elapsed > 0.0).then_some((previous, elapsed))
+    }
+
+    pub fn finish(&mut self) {
+        self.previous = mem::take(&mut self.current);
+        self.taken_at = self.started_at.take();
+    }
+}
@@ -0,0 +1,306 @@
+use std::io;
+
+use super::platform::CpuLoad;
+use super::{cgroups, container, diskio, disks, network, pressure, processes, thermal};
+use super::{Collector, Context, Schema};
+use crate::config::CgroupConfig;
+use crate::AppState;
+
+pub struct Memory;
+
+impl Collector for Memory {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn schema(&self) -> Schema {
+        Schema {
+            keys: &["memory"],
+            description: "Memory usage in bytes, from /proc/meminfo or, in container mode, the own cgroup.",
+        }
+    }
+
+    fn collect(&mut self, ctx: &Context, state: &mut AppState) -> io::Result<()> {
+        let host = ctx.platform.memory()?;
+        state.memory_usage = Some(match ctx.container() {
+            Some(dir) => container::memory(dir?, &host)?,
+            None => host,
+        });
+        Ok(())
+    }
+}
+
+pub struct Swap;
+
+impl Collector for Swap {
+    fn name(&self) -> &'static str {
+        "swap"
+    }
+
+    fn schema(&self) -> Schema {
+        Schema {
+            keys: &["swap"],
+            description: "Swap usage in bytes.",
+        }
+    }
+
+    fn collect(&mut self, ctx: &Context, state: &mut AppState) -> io::Result<()> {
+        state.swap_usage = Some(ctx.platform.swap()?);
+        Ok(())
+    }
+}
+
+pub struct Disks;
+
+impl Collector for Disks {
+    fn name(&self) -> &'static str {
+        "disks"
+    }
+
+    fn schema(&self) -> Schema {
+        Schema {
+            keys: &["disks"],
+            description: "Space and inodes of mounted filesystems, filtered by [disks].",
+        }
+    }
+
+    fn collect(&mut self, ctx: &Context, state: &mut AppState) -> io::Result<()> {
+        state.disks = Some(disks::collect(ctx.platform, &ctx.config.disks)?);
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct DiskIo(diskio::DiskIoCollector);
+
+impl Collector for DiskIo {
+    fn name(&self) -> &'static str {
+        "diskio"
+    }
+
+    fn schema(&self) -> Schema {
+        Schema {
+            keys: &["diskio"],
+            description: "Per block device I/O counters and rates, from /proc/diskstats.",
+        }
+    }
+
+    fn collect(&mut self, ctx: &Context, state: &mut AppState) -> io::Result<()> {
+        state.disk_io = Some(self.0.collect(ctx.platform)?);
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct Network(network::NetworkCollector);
+
+impl Collector for Network {
+    fn name(&self) -> &'static str {
+        "network"
+    }
+
+    fn schema(&self) -> Schema {
+        Schema {
+            keys: &["network"],
+            description: "Per interface traffic counters and rates.",
+        }
+    }
+
+    fn collect(&mut self, ctx: &Context, state: &mut AppState) -> io::Result<()> {
+        state.network = Some(self.0.collect(ctx.platform)?);
+        Ok(())
+    }
+}
+
+pub struct Load;
+
+impl Collector for Load {
+    fn name(&self) -> &'static str {
+        "load"
+    }
+
+    fn schema(&self) -> Schema {
+        Schema {
+            keys: &["load"],
+            description: "1, 5 and 15-minute load averages.",
+        }
+    }
+
+    fn collect(&mut self, ctx: &Context, state: &mut AppState) -> io::Result<()> {
+        state.load_average = Some(ctx.platform.load_average()?);
+        Ok(())
+    }
+}
+
+pub struct Uptime;
+
+impl Collector for Uptime {
+    fn name(&self) -> &'static str {
+        "uptime"
+    }
+
+    fn schema(&self) -> Schema {
+        Schema {
+            keys: &["uptime", "boot_time"],
+            description: "Seconds since boot and the Unix time the system booted at.",
+        }
+    }
+
+    fn collect(&mut self, ctx: &Context, state: &mut AppState) -> io::Result<()> {
+        let uptime = ctx.platform.uptime()?;
+        let boot_time = ctx.platform.boot_time()?;
+        state.uptime = Some(uptime.as_secs());
+        state.boot_time = Some(boot_time);
+        Ok(())
+    }
+}
+
+pub struct Pressure;
+
+impl Collector for Pressure {
+    fn name(&self) -> &'static str {
+        "pressure"
+    }
+
+    fn schema(&self) -> Schema {
+        Schema {
+            keys: &["pressure"],
+            description: "CPU, memory and IO pressure stall information, from /proc/pressure, if the kernel tracks it.",
+        }
+    }
+
+    fn collect(&mut self, ctx: &Context, state: &mut AppState) -> io::Result<()> {
+        state.pressure = pressure::collect(ctx.platform)?;
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct Cgroups(cgroups::CgroupCollector);
+
+impl Collector for Cgroups {
+    fn name(&self) -> &'static str {
+        "cgroups"
+    }
+
+    fn schema(&self) -> Schema {
+        Schema {
+            keys: &["cgroups"],
+            description: "Per cgroup CPU, memory, IO and process counts, filtered by [cgroups].",
+        }
+    }
+
+    /// Needs a cgroup v2 hierarchy, which not every host has.
+    fn enabled_by_default(&self) -> bool {
+        false
+    }
+
+    fn collect(&mut self, ctx: &Context, state: &mut AppState) -> io::Result<()> {
+        let config = CgroupConfig {
+            root: ctx.platform.path(&ctx.config.cgroups.root),
+            ..ctx.config.cgroups.clone()
+        };
+        state.cgroups = Some(self.0.collect(&config)?);
+        Ok(())
+    }
+}
+
+pub struct Thermal;
+
+impl Collector for Thermal {
+    fn name(&self) -> &'static str {
+        "thermal"
+    }
+
+    fn schema(&self) -> Schema {
+        Schema {
+            keys: &["thermal"],
+            description: "CPU, thermal zone and hardware monitoring temperatures in degrees Celsius.",
+        }
+    }
+
+    fn collect(&mut self, ctx: &Context, state: &mut AppState) -> io::Result<()> {
+        state.thermal = Some(thermal::collect(ctx.platform)?);
+        Ok(())
+    }
+}
+
+/// CPU usage being measured over the sampling window, either of the whole
+/// host or, in container mode, of our own cgroup.
+enum CpuMeasurement {
+    Host(CpuLoad),
+    Container(container::CpuTicks),
+}
+
+#[derive(Default)]
+pub struct Cpu {
+    measurement: Option<io::Result<CpuMeasurement>>,
+}
+
+impl Collector for Cpu {
+    fn name(&self) -> &'static str {
+        "cpu"
+    }
+
+    fn schema(&self) -> Schema {
+        Schema {
+            keys: &["cpu", "cores"],
+            description: "Aggregate and per-core CPU time spent in each mode over the sampling window, in percent.",
+        }
+    }
+
+    fn start(&mut self, ctx: &Context) -> bool {
+        let measurement = match ctx.container() {
+            Some(dir) => dir
+                .and_then(|dir| container::CpuTicks::start(dir.to_path_buf()))
+                .map(CpuMeasurement::Container),
+            None => ctx.platform.cpu_load().map(CpuMeasurement::Host),
+        };
+        self.measurement = Some(measurement);
+        true
+    }
+
+    fn collect(&mut self, _ctx: &Context, state: &mut AppState) -> io::Result<()> {
+        let (cpu, cores) = match self.measurement.take().ok_or_else(not_started)?? {
+            CpuMeasurement::Host(load) => load.done()?,
+            // Per-core usage is not accounted per cgroup.
+            CpuMeasurement::Container(ticks) => (ticks.finish()?, Vec::new()),
+        };
+        state.cpu_usage = Some(cpu);
+        state.cpu_cores = cores;
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct Processes {
+    ticks: Option<io::Result<processes::Ticks>>,
+}
+
+impl Collector for Processes {
+    fn name(&self) -> &'static str {
+        "processes"
+    }
+
+    fn schema(&self) -> Schema {
+        Schema {
+            keys: &[],
+            description: "Per process CPU and memory usage, served by /processes.",
+        }
+    }
+
+    fn start(&mut self, ctx: &Context) -> bool {
+        self.ticks = Some(processes::start(ctx.platform));
+        true
+    }
+
+    fn collect(&mut self, ctx: &Context, state: &mut AppState) -> io::Result<()> {
+        let ticks = self.ticks.take().ok_or_else(not_started)??;
+        state.processes = Some(processes::finish(ctx.platform, ticks)?);
+        Ok(())
+    }
+}
+
+fn not_started() -> io::Error {
+    io::Error::other("collected without being started")
+}
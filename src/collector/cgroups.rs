@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::RateTracker;
+use crate::config::CgroupConfig;
+
+/// Resource usage of a cgroup, from the files of its directory in the cgroup
+/// v2 hierarchy. A group lacks the metrics of controllers that are not
+/// enabled for it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Cgroup {
+    /// Path below the hierarchy root, `/` for the root itself.
+    pub path: String,
+    pub cpu: Option<CgroupCpu>,
+    pub memory: Option<CgroupMemory>,
+    pub io: Option<CgroupIo>,
+    pub pids: Option<CgroupPids>,
+}
+
+/// From `cpu.stat`, in microseconds since the group was created.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct CgroupCpu {
+    pub usage: u64,
+    pub user: u64,
+    pub system: u64,
+    /// CPU time used since the previous sample as a share of one core, in
+    /// percent, so a group using two full cores reports 200; `None` the
+    /// first time a group is seen.
+    pub percent: Option<f64>,
+}
+
+/// From `memory.current` and `memory.max`, in bytes.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct CgroupMemory {
+    pub current: u64,
+    /// `None` if the group is not limited.
+    pub max: Option<u64>,
+}
+
+/// From `io.stat`, summed over all devices.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct CgroupIo {
+    pub read_bytes: u64,
+    pub written_bytes: u64,
+    pub reads: u64,
+    pub writes: u64,
+}
+
+/// From `pids.current` and `pids.max`.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct CgroupPids {
+    pub current: u64,
+    /// `None` if the group is not limited.
+    pub max: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+pub struct CgroupCollector {
+    /// CPU usage of every group, to derive the CPU percentage from.
+    rates: RateTracker<u64>,
+}
+
+impl CgroupCollector {
+    /// Walks the hierarchy at `config.root` down to `config.depth` levels
+    /// below it, reporting the groups whose path contains one of
+    /// `config.include`, or all of them if it is empty.
+    pub fn collect(&mut self, config: &CgroupConfig) -> io::Result<Vec<Cgroup>> {
+        if !config.root.join("cgroup.controllers").exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("no cgroup v2 hierarchy at {}", config.root.display()),
+            ));
+        }
+        let mut paths = Vec::new();
+        walk(&config.root, "/", config.depth, &mut paths)?;
+        self.rates.start();
+        let mut cgroups = Vec::new();
+        for path in paths {
+            if !config.include.is_empty() && !config.include.iter().any(|name| path.contains(name.as_str())) {
+                continue;
+            }
+            let dir = config.root.join(path.trim_start_matches('/'));
+            let mut cpu = cpu(&dir);
+            if let Some(cpu) = &mut cpu {
+                if let Some((previous, elapsed)) = self.rates.track(&path, cpu.usage) {
+                    let used = cpu.usage.saturating_sub(previous) as f64 / 1_000_000.0;
+                    cpu.percent = Some(used / elapsed * 100.0);
+                }
+            }
+            cgroups.push(Cgroup {
+                memory: memory(&dir),
+                io: io_stat(&dir),
+                pids: pids(&dir),
+                cpu,
+                path,
+            });
+        }
+
+        self.rates.finish();
+        Ok(cgroups)
+    }
+}
+
+/// Appends `path` and the groups below it, up to `depth` levels down, in
+/// sorted order.
+fn walk(dir: &Path, path: &str, depth: usize, paths: &mut Vec<String>) -> io::Result<()> {
+    paths.push(path.to_string());
+    if depth == 0 {
+        return Ok(());
+    }
+    let mut children: Vec<String> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                entry.file_type().ok()?.is_dir().then(|| entry.file_name().to_string_lossy().into_owned())
+            })
+            .collect(),
+        // The group may have been removed since its parent was listed.
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    children.sort();
+    for child in children {
+        let child_path = format!("{}/{}", path.trim_end_matches('/'), child);
+        walk(&dir.join(&child), &child_path, depth - 1, paths)?;
+    }
+    Ok(())
+}
+
+fn cpu(dir: &Path) -> Option<CgroupCpu> {
+    let stat = keyed(&dir.join("cpu.stat"))?;
+    Some(CgroupCpu {
+        usage: *stat.get("usage_usec")?,
+        user: stat.get("user_usec").copied().unwrap_or(0),
+        system: stat.get("system_usec").copied().unwrap_or(0),
+        percent: None,
+    })
+}
+
+fn memory(dir: &Path) -> Option<CgroupMemory> {
+    Some(CgroupMemory {
+        current: read_value(&dir.join("memory.current"))??,
+        max: read_value(&dir.join("memory.max")).flatten(),
+    })
+}
+
+fn pids(dir: &Path) -> Option<CgroupPids> {
+    Some(CgroupPids {
+        current: read_value(&dir.join("pids.current"))??,
+        max: read_value(&dir.join("pids.max")).flatten(),
+    })
+}
+
+/// Sums the per-device lines of `io.stat`, such as
+/// `8:0 rbytes=1024 wbytes=0 rios=1 wios=0 dbytes=0 dios=0`.
+fn io_stat(dir: &Path) -> Option<CgroupIo> {
+    let stat = fs::read_to_string(dir.join("io.stat")).ok()?;
+    let mut io = CgroupIo::default();
+    for field in stat.lines().flat_map(|line| line.split_whitespace().skip(1)) {
+        let Some((key, value)) = field.split_once('=') else {
+            continue;
+        };
+        let value: u64 = value.parse().unwrap_or(0);
+        match key {
+            "rbytes" => io.read_bytes += value,
+            "wbytes" => io.written_bytes += value,
+            "rios" => io.reads += value,
+            "wios" => io.writes += value,
+            _ => {}
+        }
+    }
+    Some(io)
+}
+
+/// Reads a file of `key value` lines such as `cpu.stat`.
+pub(super) fn keyed(path: &Path) -> Option<HashMap<String, u64>> {
+    let contents = fs::read_to_string(path).ok()?;
+    Some(
+        contents
+            .lines()
+            .filter_map(|line| {
+                let (key, value) = line.split_once(' ')?;
+                Some((key.to_string(), value.trim().parse().ok()?))
+            })
+            .collect(),
+    )
+}
+
+/// Reads a single-value file such as `memory.max`: `None` if it cannot be
+/// read, `Some(None)` if it holds `max`, meaning no limit.
+pub(super) fn read_value(path: &Path) -> Option<Option<u64>> {
+    let value = fs::read_to_string(path).ok()?;
+    match value.trim() {
+        "max" => Some(None),
+        value => Some(Some(value.parse().ok()?)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn config(depth: usize, include: &[&str]) -> CgroupConfig {
+        CgroupConfig {
+            root: PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/cgroup"),
+            depth,
+            include: include.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    fn paths(cgroups: &[Cgroup]) -> Vec<&str> {
+        cgroups.iter().map(|c| c.path.as_str()).collect()
+    }
+
+    #[test]
+    fn walks_down_to_depth() {
+        let mut collector = CgroupCollector::default();
+        let cgroups = collector.collect(&config(1, &[])).unwrap();
+        assert_eq!(paths(&cgroups), ["/", "/system.slice", "/user.slice"]);
+
+        let cgroups = collector.collect(&config(2, &[])).unwrap();
+        assert_eq!(
+            paths(&cgroups),
+            ["/", "/system.slice", "/system.slice/docker-web.scope", "/system.slice/nginx.service", "/user.slice"]
+        );
+    }
+
+    #[test]
+    fn filters_by_name() {
+        let cgroups = CgroupCollector::default().collect(&config(2, &["docker", "user"])).unwrap();
+        assert_eq!(paths(&cgroups), ["/system.slice/docker-web.scope", "/user.slice"]);
+    }
+
+    #[test]
+    fn reads_controller_files() {
+        let cgroups = CgroupCollector::default().collect(&config(2, &["docker"])).unwrap();
+        let web = &cgroups[0];
+
+        let cpu = web.cpu.unwrap();
+        assert_eq!((cpu.usage, cpu.user, cpu.system), (2_500_000, 2_000_000, 500_000));
+        assert_eq!(cpu.percent, None);
+
+        let memory = web.memory.unwrap();
+        assert_eq!((memory.current, memory.max), (104_857_600, Some(536_870_912)));
+
+        let io = web.io.unwrap();
+        assert_eq!((io.read_bytes, io.written_bytes, io.reads, io.writes), (5120, 2048, 5, 2));
+
+        let pids = web.pids.unwrap();
+        assert_eq!((pids.current, pids.max), (12, None));
+    }
+
+    #[test]
+    fn root_lacks_memory_and_pids() {
+        let cgroups = CgroupCollector::default().collect(&config(0, &[])).unwrap();
+        assert_eq!(paths(&cgroups), ["/"]);
+        assert!(cgroups[0].cpu.is_some());
+        assert!(cgroups[0].memory.is_none());
+        assert!(cgroups[0].pids.is_none());
+    }
+
+    #[test]
+    fn derives_cpu_percent_on_second_sample() {
+        let mut collector = CgroupCollector::default();
+        collector.collect(&config(2, &[])).unwrap();
+        let cgroups = collector.collect(&config(2, &[])).unwrap();
+        // The fixture does not change between samples.
+        assert!(cgroups.iter().all(|c| c.cpu.is_none_or(|cpu| cpu.percent == Some(0.0))));
+    }
+
+    #[test]
+    fn requires_cgroup_v2() {
+        let mut config = config(1, &[]);
+        config.root = config.root.join("user.slice");
+        let e = CgroupCollector::default().collect(&config).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::Unsupported);
+    }
+}
@@ -0,0 +1,186 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use tokio::time::Instant;
+
+use super::cgroups::{keyed, read_value};
+use super::platform::Platform;
+use crate::{Memory, CPU};
+
+/// Finds the directory of our own cgroup in the cgroup v2 hierarchy mounted
+/// at `root`, from the `0::<path>` line of `/proc/self/cgroup`. Inside a
+/// container with its own cgroup namespace the path is `/`, the root itself.
+pub fn detect(platform: &dyn Platform, root: &Path) -> io::Result<PathBuf> {
+    let root = platform.path(root);
+    let cgroups = fs::read_to_string(platform.path("/proc/self/cgroup"))?;
+    let path = cgroups
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "not in a cgroup v2 hierarchy"))?;
+    let dir = root.join(path.trim().trim_start_matches('/'));
+    if !dir.join("cgroup.controllers").exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("own cgroup {} not found below {}", path.trim(), root.display()),
+        ));
+    }
+    Ok(dir)
+}
+
+/// Memory usage of the cgroup in `dir`, measured against its `memory.max`,
+/// or against the host's total memory if it is not limited.
+///
+/// `used` is the working set, `memory.current` minus the inactive page cache
+/// the kernel can reclaim, as reported by `docker stats` and the Kubernetes
+/// metrics API.
+pub fn memory(dir: &Path, host: &Memory) -> io::Result<Memory> {
+    let not_found = |file: &str| {
+        io::Error::new(io::ErrorKind::NotFound, format!("no {} in {}", file, dir.display()))
+    };
+    let current = read_value(&dir.join("memory.current"))
+        .flatten()
+        .ok_or_else(|| not_found("memory.current"))?;
+    let total = match read_value(&dir.join("memory.max")).flatten() {
+        Some(max) => max.min(host.total),
+        None => host.total,
+    };
+    let stat = keyed(&dir.join("memory.stat")).ok_or_else(|| not_found("memory.stat"))?;
+    let stat = |key: &str| stat.get(key).copied().unwrap_or(0);
+    let used = current.saturating_sub(stat("inactive_file"));
+    Ok(Memory {
+        used,
+        total,
+        free: total.saturating_sub(current),
+        available: total.saturating_sub(used),
+        buffers: 0,
+        cached: stat("file"),
+        shared: stat("shmem"),
+        slab: stat("slab"),
+        dirty: stat("file_dirty"),
+        writeback: stat("file_writeback"),
+    })
+}
+
+/// CPU time used by a cgroup, taken at the start of the sampling window.
+#[derive(Debug)]
+pub struct CpuTicks {
+    dir: PathBuf,
+    user: u64,
+    system: u64,
+    taken_at: Instant,
+}
+
+impl CpuTicks {
+    pub fn start(dir: PathBuf) -> io::Result<Self> {
+        let (user, system) = cpu_usage(&dir)?;
+        Ok(CpuTicks {
+            dir,
+            user,
+            system,
+            taken_at: Instant::now(),
+        })
+    }
+
+    /// CPU usage of the cgroup since `start` as a share of its `cpu.max`
+    /// quota, or of all CPUs available to us if it has none.
+    pub fn finish(self) -> io::Result<CPU> {
+        let (user, system) = cpu_usage(&self.dir)?;
+        let elapsed = self.taken_at.elapsed().as_secs_f64();
+        let capacity = cpu_limit(&self.dir)? * elapsed * 1_000_000.0;
+        let percent = |before: u64, after: u64| {
+            if capacity > 0.0 {
+                (after.saturating_sub(before) as f64 / capacity * 100.0) as f32
+            } else {
+                0.0
+            }
+        };
+        let user = percent(self.user, user);
+        let system = percent(self.system, system);
+        Ok(CPU {
+            user,
+            nice: 0.0,
+            interrupt: 0.0,
+            system,
+            idle: (100.0 - user - system).max(0.0),
+        })
+    }
+}
+
+/// User and system time from `cpu.stat`, in microseconds.
+fn cpu_usage(dir: &Path) -> io::Result<(u64, u64)> {
+    let stat = keyed(&dir.join("cpu.stat"))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no cpu.stat in {}", dir.display())))?;
+    Ok((
+        stat.get("user_usec").copied().unwrap_or(0),
+        stat.get("system_usec").copied().unwrap_or(0),
+    ))
+}
+
+/// Number of CPUs the cgroup may use, from `cpu.max` such as `150000 100000`
+/// for one and a half CPUs, or `max 100000` for no limit.
+fn cpu_limit(dir: &Path) -> io::Result<f64> {
+    let available = || thread::available_parallelism().map(|n| n.get() as f64).unwrap_or(1.0);
+    let Ok(max) = fs::read_to_string(dir.join("cpu.max")) else {
+        // The root cgroup has no cpu.max.
+        return Ok(available());
+    };
+    let mut fields = max.split_whitespace();
+    match (fields.next(), fields.next().and_then(|p| p.parse::<f64>().ok())) {
+        (Some("max"), _) => Ok(available()),
+        (Some(quota), Some(period)) if period > 0.0 => quota
+            .parse::<f64>()
+            .map(|quota| quota / period)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "unexpected format of cpu.max")),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, "unexpected format of cpu.max")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(path: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/cgroup").join(path)
+    }
+
+    fn host(total: u64) -> Memory {
+        Memory {
+            total,
+            ..Memory::default()
+        }
+    }
+
+    #[test]
+    fn memory_against_limit() {
+        let memory = memory(&fixture("system.slice/docker-web.scope"), &host(8 << 30)).unwrap();
+        assert_eq!(memory.total, 536_870_912);
+        // memory.current minus inactive_file.
+        assert_eq!(memory.used, 104_857_600 - 41_943_040);
+        assert_eq!(memory.available, 536_870_912 - memory.used);
+        assert_eq!(memory.free, 536_870_912 - 104_857_600);
+        assert_eq!((memory.cached, memory.shared, memory.slab), (62_914_560, 1_048_576, 2_097_152));
+    }
+
+    #[test]
+    fn memory_limit_capped_at_host_total() {
+        let memory = memory(&fixture("system.slice/docker-web.scope"), &host(256 << 20)).unwrap();
+        assert_eq!(memory.total, 256 << 20);
+    }
+
+    #[test]
+    fn cpu_limit_from_quota() {
+        assert_eq!(cpu_limit(&fixture("system.slice/docker-web.scope")).unwrap(), 0.5);
+        let available = thread::available_parallelism().unwrap().get() as f64;
+        assert_eq!(cpu_limit(&fixture("system.slice/nginx.service")).unwrap(), available);
+    }
+
+    #[test]
+    fn cpu_usage_against_quota() {
+        let ticks = CpuTicks::start(fixture("system.slice/docker-web.scope")).unwrap();
+        let cpu = ticks.finish().unwrap();
+        // The fixture does not change, so nothing was used.
+        assert_eq!((cpu.user, cpu.system, cpu.idle), (0.0, 0.0, 100.0));
+    }
+}
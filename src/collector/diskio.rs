@@ -0,0 +1,207 @@
+use std::io;
+
+use serde::Serialize;
+use systemstat::BlockDeviceStats;
+
+use super::platform::Platform;
+use super::RateTracker;
+
+/// `/proc/diskstats` counts in sectors of 512 bytes, whatever the device's
+/// actual sector size.
+const SECTOR_SIZE: u64 = 512;
+
+/// Per-device counters since boot, from `/proc/diskstats`.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct IoCounters {
+    /// Completed reads.
+    pub reads: u64,
+    /// Completed writes.
+    pub writes: u64,
+    pub read_bytes: u64,
+    pub written_bytes: u64,
+    /// Milliseconds spent on reads, summed over all reads.
+    pub read_time: u64,
+    /// Milliseconds spent on writes, summed over all writes.
+    pub write_time: u64,
+    /// Milliseconds during which the device had I/O in progress.
+    pub io_time: u64,
+    /// Requests currently in progress.
+    pub in_flight: u64,
+}
+
+/// Activity of a device between two samples.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct IoRates {
+    pub read_iops: f64,
+    pub write_iops: f64,
+    /// Bytes read per second.
+    pub read_throughput: f64,
+    /// Bytes written per second.
+    pub write_throughput: f64,
+    /// Average milliseconds a read took, including time spent queued; `None`
+    /// if there were no reads.
+    pub read_await: Option<f64>,
+    /// Average milliseconds a write took, including time spent queued;
+    /// `None` if there were no writes.
+    pub write_await: Option<f64>,
+    /// Share of the time the device was busy, in percent.
+    pub utilization: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Device {
+    pub name: String,
+    pub total: IoCounters,
+    /// Activity since the previous sample; `None` the first time a device is
+    /// seen.
+    pub rate: Option<IoRates>,
+}
+
+#[derive(Debug, Default)]
+pub struct DiskIoCollector {
+    rates: RateTracker<IoCounters>,
+}
+
+impl DiskIoCollector {
+    /// Reports every block device and partition that has done any I/O since
+    /// boot, which leaves out unused loop and ram devices.
+    pub fn collect(&mut self, platform: &dyn Platform) -> io::Result<Vec<Device>> {
+        let stats = platform.block_device_statistics()?;
+        self.rates.start();
+        let mut devices = Vec::new();
+        for (name, stats) in stats {
+            let total = counters(&stats);
+            if total.reads == 0 && total.writes == 0 {
+                continue;
+            }
+            let rate = self.rates.track(&name, total).map(|(previous, elapsed)| rate(&previous, &total, elapsed));
+            devices.push(Device { name, total, rate });
+        }
+        self.rates.finish();
+        Ok(devices)
+    }
+}
+
+fn counters(stats: &BlockDeviceStats) -> IoCounters {
+    IoCounters {
+        reads: stats.read_ios as u64,
+        writes: stats.write_ios as u64,
+        read_bytes: stats.read_sectors as u64 * SECTOR_SIZE,
+        written_bytes: stats.write_sectors as u64 * SECTOR_SIZE,
+        read_time: stats.read_ticks as u64,
+        write_time: stats.write_ticks as u64,
+        io_time: stats.io_ticks as u64,
+        in_flight: stats.in_flight as u64,
+    }
+}
+
+fn rate(previous: &IoCounters, current: &IoCounters, elapsed: f64) -> IoRates {
+    // A counter that went backwards was reset, e.g. by re-attaching the device.
+    let delta = |before: u64, after: u64| after.saturating_sub(before);
+    let per_sec = |before: u64, after: u64| delta(before, after) as f64 / elapsed;
+    let await_ms = |ios: u64, time: u64| (ios > 0).then(|| time as f64 / ios as f64);
+    let reads = delta(previous.reads, current.reads);
+    let writes = delta(previous.writes, current.writes);
+    IoRates {
+        read_iops: reads as f64 / elapsed,
+        write_iops: writes as f64 / elapsed,
+        read_throughput: per_sec(previous.read_bytes, current.read_bytes),
+        write_throughput: per_sec(previous.written_bytes, current.written_bytes),
+        read_await: await_ms(reads, delta(previous.read_time, current.read_time)),
+        write_await: await_ms(writes, delta(previous.write_time, current.write_time)),
+        // io_time is in milliseconds and elapsed in seconds.
+        utilization: (per_sec(previous.io_time, current.io_time) / 10.0).min(100.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::time::{self, Duration};
+
+    use super::*;
+    use crate::collector::platform::Fake;
+
+    /// A device with the given read counters and `io_ticks`, and 10 writes.
+    fn stats(name: &str, read_ios: usize, read_sectors: usize, read_ticks: usize, io_ticks: usize) -> BlockDeviceStats {
+        BlockDeviceStats {
+            name: name.to_string(),
+            read_ios,
+            read_merges: 0,
+            read_sectors,
+            read_ticks,
+            write_ios: 10,
+            write_merges: 0,
+            write_sectors: 80,
+            write_ticks: 40,
+            in_flight: 1,
+            io_ticks,
+            time_in_queue: 0,
+        }
+    }
+
+    fn platform(devices: &[BlockDeviceStats]) -> Fake {
+        Fake {
+            block_devices: devices.iter().map(|d| (d.name.clone(), d.clone())).collect(),
+            ..Fake::default()
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn derives_rates_from_two_samples() {
+        let mut collector = DiskIoCollector::default();
+        let first = collector.collect(&platform(&[stats("sda", 100, 2000, 500, 1000)])).unwrap();
+        assert_eq!(first[0].total.read_bytes, 2000 * 512);
+        assert_eq!(first[0].total.in_flight, 1);
+        assert!(first[0].rate.is_none());
+
+        time::advance(Duration::from_secs(2)).await;
+        let second = collector.collect(&platform(&[stats("sda", 300, 6000, 1500, 2000)])).unwrap();
+        let rate = second[0].rate.unwrap();
+        assert_eq!(rate.read_iops, 100.0);
+        assert_eq!(rate.read_throughput, (4000 * 512 / 2) as f64);
+        // 1000 ms spent on 200 reads.
+        assert_eq!(rate.read_await, Some(5.0));
+        // No writes in between.
+        assert_eq!((rate.write_iops, rate.write_await), (0.0, None));
+        // Busy for 1000 ms of 2 seconds.
+        assert_eq!(rate.utilization, 50.0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn caps_utilization() {
+        let mut collector = DiskIoCollector::default();
+        collector.collect(&platform(&[stats("nvme0n1", 100, 2000, 500, 1000)])).unwrap();
+        time::advance(Duration::from_secs(1)).await;
+        // Devices serving requests in parallel can report more busy time
+        // than wall time.
+        let devices = collector.collect(&platform(&[stats("nvme0n1", 100, 2000, 500, 2500)])).unwrap();
+        assert_eq!(devices[0].rate.unwrap().utilization, 100.0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reset_counters_count_as_no_activity() {
+        let mut collector = DiskIoCollector::default();
+        collector.collect(&platform(&[stats("sdb", 300, 6000, 1500, 2000)])).unwrap();
+        time::advance(Duration::from_secs(1)).await;
+        let devices = collector.collect(&platform(&[stats("sdb", 100, 2000, 500, 1000)])).unwrap();
+        let rate = devices[0].rate.unwrap();
+        assert_eq!((rate.read_iops, rate.read_throughput, rate.utilization), (0.0, 0.0, 0.0));
+        assert_eq!(rate.read_await, None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn skips_idle_and_forgets_removed_devices() {
+        let mut collector = DiskIoCollector::default();
+        let mut loop0 = stats("loop0", 0, 0, 0, 0);
+        loop0.write_ios = 0;
+        let devices = collector.collect(&platform(&[stats("sda", 100, 2000, 500, 1000), loop0])).unwrap();
+        assert_eq!(devices.iter().map(|d| d.name.as_str()).collect::<Vec<_>>(), ["sda"]);
+
+        time::advance(Duration::from_secs(1)).await;
+        collector.collect(&platform(&[])).unwrap();
+        time::advance(Duration::from_secs(1)).await;
+        // Seen again after a sample without it, so there is nothing to compare with.
+        let devices = collector.collect(&platform(&[stats("sda", 200, 2000, 500, 1000)])).unwrap();
+        assert!(devices[0].rate.is_none());
+    }
+}
@@ -0,0 +1,119 @@
+use std::io;
+
+use serde::Serialize;
+use systemstat::Filesystem;
+
+use super::platform::Platform;
+use crate::config::DiskFilter;
+
+/// Space and inode usage of a mounted filesystem.
+#[derive(Debug, Clone, Serialize)]
+pub struct Disk {
+    pub mount: String,
+    pub device: String,
+    pub fs_type: String,
+    pub total: u64,
+    pub free: u64,
+    /// Free bytes available to unprivileged users.
+    pub available: u64,
+    pub inodes_total: u64,
+    pub inodes_free: u64,
+    /// Free inodes available to unprivileged users.
+    pub inodes_available: u64,
+}
+
+/// Lists the mounted filesystems that pass `filter`.
+pub fn collect(platform: &dyn Platform, filter: &DiskFilter) -> io::Result<Vec<Disk>> {
+    let mounts = platform.mounts()?;
+    Ok(mounts
+        .iter()
+        .filter(|fs| filter.allows(&fs.fs_type))
+        .map(disk)
+        .collect())
+}
+
+fn disk(fs: &Filesystem) -> Disk {
+    Disk {
+        mount: fs.fs_mounted_on.clone(),
+        device: fs.fs_mounted_from.clone(),
+        fs_type: fs.fs_type.clone(),
+        total: fs.total.as_u64(),
+        free: fs.free.as_u64(),
+        available: fs.avail.as_u64(),
+        inodes_total: fs.files_total as u64,
+        inodes_free: fs.files_total.saturating_sub(fs.files) as u64,
+        inodes_available: fs.files_avail as u64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use systemstat::ByteSize;
+
+    use super::*;
+    use crate::collector::platform::Fake;
+
+    fn filesystem(mount: &str, fs_type: &str) -> Filesystem {
+        Filesystem {
+            files: 1000,
+            files_total: 65_536,
+            files_avail: 60_000,
+            free: ByteSize::gib(40),
+            avail: ByteSize::gib(35),
+            total: ByteSize::gib(100),
+            name_max: 255,
+            fs_type: fs_type.to_string(),
+            fs_mounted_from: "/dev/sda1".to_string(),
+            fs_mounted_on: mount.to_string(),
+        }
+    }
+
+    fn platform() -> Fake {
+        Fake {
+            mounts: vec![
+                filesystem("/", "ext4"),
+                filesystem("/run", "tmpfs"),
+                filesystem("/var/lib/docker/overlay2/merged", "overlay"),
+                filesystem("/data", "xfs"),
+            ],
+            ..Fake::default()
+        }
+    }
+
+    fn mounts(disks: &[Disk]) -> Vec<&str> {
+        disks.iter().map(|d| d.mount.as_str()).collect()
+    }
+
+    #[test]
+    fn excludes_pseudo_filesystems_by_default() {
+        let disks = collect(&platform(), &DiskFilter::default()).unwrap();
+        assert_eq!(mounts(&disks), ["/", "/data"]);
+    }
+
+    #[test]
+    fn includes_only_listed_types() {
+        let filter = DiskFilter {
+            include_fs_types: vec!["ext4".into(), "overlay".into()],
+            exclude_fs_types: Vec::new(),
+        };
+        let disks = collect(&platform(), &filter).unwrap();
+        assert_eq!(mounts(&disks), ["/", "/var/lib/docker/overlay2/merged"]);
+
+        let filter = DiskFilter {
+            include_fs_types: Vec::new(),
+            exclude_fs_types: vec!["xfs".into()],
+        };
+        let disks = collect(&platform(), &filter).unwrap();
+        assert_eq!(mounts(&disks), ["/", "/run", "/var/lib/docker/overlay2/merged"]);
+    }
+
+    #[test]
+    fn reports_space_and_inodes() {
+        let disks = collect(&platform(), &DiskFilter::default()).unwrap();
+        let root = &disks[0];
+        assert_eq!((root.device.as_str(), root.fs_type.as_str()), ("/dev/sda1", "ext4"));
+        assert_eq!((root.total, root.free, root.available), (100 << 30, 40 << 30, 35 << 30));
+        // systemstat's `files` counts the inodes in use.
+        assert_eq!((root.inodes_total, root.inodes_free, root.inodes_available), (65_536, 64_536, 60_000));
+    }
+}
@@ -0,0 +1,154 @@
+use std::{fs, io};
+
+use serde::Serialize;
+
+use super::platform::Platform;
+use super::RateTracker;
+
+/// Per-interface traffic counters, either totals since boot or per-second
+/// rates between two samples.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct Counters<T> {
+    pub rx_bytes: T,
+    pub tx_bytes: T,
+    pub rx_packets: T,
+    pub tx_packets: T,
+    pub rx_errors: T,
+    pub tx_errors: T,
+    pub rx_drops: T,
+    pub tx_drops: T,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Interface {
+    pub name: String,
+    pub total: Counters<u64>,
+    /// Per-second rates since the previous sample; `None` the first time an
+    /// interface is seen.
+    pub rate: Option<Counters<f64>>,
+}
+
+#[derive(Debug, Default)]
+pub struct NetworkCollector {
+    rates: RateTracker<Counters<u64>>,
+}
+
+impl NetworkCollector {
+    pub fn collect(&mut self, platform: &dyn Platform) -> io::Result<Vec<Interface>> {
+        let networks = platform.network_stats()?;
+        self.rates.start();
+        let mut interfaces = Vec::with_capacity(networks.len());
+        for (name, stats) in &networks {
+            let total = Counters {
+                rx_bytes: stats.rx_bytes.as_u64(),
+                tx_bytes: stats.tx_bytes.as_u64(),
+                rx_packets: stats.rx_packets,
+                tx_packets: stats.tx_packets,
+                rx_errors: stats.rx_errors,
+                tx_errors: stats.tx_errors,
+                rx_drops: dropped(platform, name, "rx"),
+                tx_drops: dropped(platform, name, "tx"),
+            };
+            let rate = self.rates.track(name, total).map(|(previous, elapsed)| rate(&previous, &total, elapsed));
+            interfaces.push(Interface { name: name.clone(), total, rate });
+        }
+        self.rates.finish();
+        Ok(interfaces)
+    }
+}
+
+/// systemstat does not report dropped packets, so read them from sysfs.
+fn dropped(platform: &dyn Platform, interface: &str, direction: &str) -> u64 {
+    let path = format!("/sys/class/net/{}/statistics/{}_dropped", interface, direction);
+    fs::read_to_string(platform.path(path))
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+fn rate(previous: &Counters<u64>, current: &Counters<u64>, elapsed: f64) -> Counters<f64> {
+    // A counter that went backwards was reset, e.g. by re-creating the interface.
+    let per_sec = |before: u64, after: u64| after.saturating_sub(before) as f64 / elapsed;
+    Counters {
+        rx_bytes: per_sec(previous.rx_bytes, current.rx_bytes),
+        tx_bytes: per_sec(previous.tx_bytes, current.tx_bytes),
+        rx_packets: per_sec(previous.rx_packets, current.rx_packets),
+        tx_packets: per_sec(previous.tx_packets, current.tx_packets),
+        rx_errors: per_sec(previous.rx_errors, current.rx_errors),
+        tx_errors: per_sec(previous.tx_errors, current.tx_errors),
+        rx_drops: per_sec(previous.rx_drops, current.rx_drops),
+        tx_drops: per_sec(previous.tx_drops, current.tx_drops),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use systemstat::{ByteSize, NetworkStats};
+    use tokio::time::{self, Duration};
+
+    use super::*;
+    use crate::collector::platform::Fake;
+
+    fn stats(rx_bytes: u64, tx_bytes: u64, rx_packets: u64) -> NetworkStats {
+        NetworkStats {
+            rx_bytes: ByteSize::b(rx_bytes),
+            tx_bytes: ByteSize::b(tx_bytes),
+            rx_packets,
+            tx_packets: 10,
+            rx_errors: 1,
+            tx_errors: 0,
+        }
+    }
+
+    /// Reads dropped packets from the capture in `tests/fixtures/replay`.
+    fn platform(interfaces: &[(&str, NetworkStats)]) -> Fake {
+        Fake {
+            root: PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/replay"),
+            networks: interfaces.iter().map(|(name, stats)| (name.to_string(), stats.clone())).collect(),
+            ..Fake::default()
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn derives_rates_from_two_samples() {
+        let mut collector = NetworkCollector::default();
+        let first = collector.collect(&platform(&[("eth0", stats(1000, 500, 10))])).unwrap();
+        assert_eq!((first[0].total.rx_bytes, first[0].total.rx_errors), (1000, 1));
+        assert_eq!(first[0].total.rx_drops, 12);
+        assert!(first[0].rate.is_none());
+
+        time::advance(Duration::from_secs(4)).await;
+        let second = collector.collect(&platform(&[("eth0", stats(9000, 2500, 30))])).unwrap();
+        let rate = second[0].rate.unwrap();
+        assert_eq!((rate.rx_bytes, rate.tx_bytes, rate.rx_packets), (2000.0, 500.0, 5.0));
+        assert_eq!((rate.tx_packets, rate.rx_errors, rate.rx_drops), (0.0, 0.0, 0.0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reset_counters_count_as_no_traffic() {
+        let mut collector = NetworkCollector::default();
+        collector.collect(&platform(&[("eth0", stats(9000, 2500, 30))])).unwrap();
+        time::advance(Duration::from_secs(1)).await;
+        // The interface was re-created and counts from zero again.
+        let interfaces = collector.collect(&platform(&[("eth0", stats(1000, 3500, 10))])).unwrap();
+        let rate = interfaces[0].rate.unwrap();
+        assert_eq!((rate.rx_bytes, rate.tx_bytes, rate.rx_packets), (0.0, 1000.0, 0.0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn new_interface_has_no_rate() {
+        let mut collector = NetworkCollector::default();
+        collector.collect(&platform(&[("eth0", stats(1000, 500, 10))])).unwrap();
+        time::advance(Duration::from_secs(1)).await;
+        let interfaces = collector
+            .collect(&platform(&[("eth0", stats(2000, 500, 20)), ("wg0", stats(100, 100, 1))]))
+            .unwrap();
+        assert_eq!(interfaces[0].rate.unwrap().rx_bytes, 1000.0);
+        assert_eq!(interfaces[1].name, "wg0");
+        // Not in the capture, so no drops can be read.
+        assert_eq!(interfaces[1].total.rx_drops, 0);
+        assert!(interfaces[1].rate.is_none());
+    }
+}
@@ -0,0 +1,422 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+use systemstat::{
+    saturating_sub_bytes, BlockDeviceStats, ByteSize, CPULoad, CpuTime, DelayedMeasurement, Filesystem,
+    NetworkStats, Platform as _, System,
+};
+use tokio::time::Duration;
+
+use crate::{LoadAverage, Memory, Swap, CPU};
+
+/// The system calls the collectors make, so that tests can replace the live
+/// system with canned values.
+pub trait Platform: Send + Sync {
+    fn memory(&self) -> io::Result<Memory>;
+    fn swap(&self) -> io::Result<Swap>;
+    /// Starts measuring aggregate and per-core CPU usage.
+    fn cpu_load(&self) -> io::Result<CpuLoad>;
+    fn load_average(&self) -> io::Result<LoadAverage>;
+    fn uptime(&self) -> io::Result<Duration>;
+    /// Unix time the system booted at.
+    fn boot_time(&self) -> io::Result<i64>;
+    fn mounts(&self) -> io::Result<Vec<Filesystem>>;
+    /// Traffic counters by interface name.
+    fn network_stats(&self) -> io::Result<BTreeMap<String, NetworkStats>>;
+    /// I/O counters by block device name.
+    fn block_device_statistics(&self) -> io::Result<BTreeMap<String, BlockDeviceStats>>;
+    /// CPU package temperature in degrees Celsius.
+    fn cpu_temp(&self) -> io::Result<f32>;
+
+    /// Directory `/proc` and `/sys` are found in, which collectors reading
+    /// them directly go through `path` for.
+    fn root(&self) -> &Path {
+        Path::new("/")
+    }
+}
+
+impl dyn Platform + '_ {
+    /// Where the absolute `path`, e.g. `/proc/stat`, is found on this platform.
+    pub fn path(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.root().join(path.as_ref().strip_prefix("/").unwrap_or(path.as_ref()))
+    }
+}
+
+/// A CPU usage measurement in progress.
+pub enum CpuLoad {
+    Live(DelayedMeasurement<CPULoad>, DelayedMeasurement<Vec<CPULoad>>),
+    /// Usage known up front, aggregate and per core.
+    Fixed(CPU, Vec<CPU>),
+}
+
+impl CpuLoad {
+    /// Ends the measurement, returning the aggregate and per-core usage since
+    /// it started.
+    pub fn done(self) -> io::Result<(CPU, Vec<CPU>)> {
+        match self {
+            CpuLoad::Live(cpu, cores) => Ok((percent(&cpu.done()?), cores.done()?.iter().map(percent).collect())),
+            CpuLoad::Fixed(cpu, cores) => Ok((cpu, cores)),
+        }
+    }
+}
+
+/// The machine StatMonitor runs on, through systemstat.
+pub struct Live(System);
+
+impl Default for Live {
+    fn default() -> Self {
+        Live(System::new())
+    }
+}
+
+impl Platform for Live {
+    fn memory(&self) -> io::Result<Memory> {
+        let mem = self.0.memory()?;
+        Ok(memory(&mem.platform_memory.meminfo))
+    }
+
+    fn swap(&self) -> io::Result<Swap> {
+        let swap = self.0.swap()?;
+        Ok(Swap {
+            used: saturating_sub_bytes(swap.total, swap.free).as_u64(),
+            total: swap.total.as_u64(),
+        })
+    }
+
+    fn cpu_load(&self) -> io::Result<CpuLoad> {
+        Ok(CpuLoad::Live(self.0.cpu_load_aggregate()?, self.0.cpu_load()?))
+    }
+
+    fn load_average(&self) -> io::Result<LoadAverage> {
+        let load = self.0.load_average()?;
+        Ok(LoadAverage {
+            one: load.one,
+            five: load.five,
+            fifteen: load.fifteen,
+        })
+    }
+
+    fn uptime(&self) -> io::Result<Duration> {
+        self.0.uptime()
+    }
+
+    fn boot_time(&self) -> io::Result<i64> {
+        Ok(self.0.boot_time()?.unix_timestamp())
+    }
+
+    fn mounts(&self) -> io::Result<Vec<Filesystem>> {
+        self.0.mounts()
+    }
+
+    fn network_stats(&self) -> io::Result<BTreeMap<String, NetworkStats>> {
+        self.0
+            .networks()?
+            .into_keys()
+            .map(|name| Ok((name.clone(), self.0.network_stats(&name)?)))
+            .collect()
+    }
+
+    fn block_device_statistics(&self) -> io::Result<BTreeMap<String, BlockDeviceStats>> {
+        self.0.block_device_statistics()
+    }
+
+    fn cpu_temp(&self) -> io::Result<f32> {
+        self.0.cpu_temp()
+    }
+}
+
+/// Canned values for tests. Metrics left as `None` fail with `Unsupported`;
+/// lists default to empty.
+#[derive(Debug, Clone, Default)]
+pub struct Fake {
+    /// Directory standing in for `/`, such as a fixture, for the collectors
+    /// that read `/proc` and `/sys` directly.
+    pub root: PathBuf,
+    pub memory: Option<Memory>,
+    pub swap: Option<Swap>,
+    /// Aggregate and per-core CPU usage.
+    pub cpu: Option<(CPU, Vec<CPU>)>,
+    pub load: Option<LoadAverage>,
+    pub uptime: Option<Duration>,
+    pub boot_time: Option<i64>,
+    pub mounts: Vec<Filesystem>,
+    pub networks: BTreeMap<String, NetworkStats>,
+    pub block_devices: BTreeMap<String, BlockDeviceStats>,
+    pub cpu_temp: Option<f32>,
+}
+
+impl Platform for Fake {
+    fn memory(&self) -> io::Result<Memory> {
+        faked(&self.memory)
+    }
+
+    fn swap(&self) -> io::Result<Swap> {
+        faked(&self.swap)
+    }
+
+    fn cpu_load(&self) -> io::Result<CpuLoad> {
+        faked(&self.cpu).map(|(cpu, cores)| CpuLoad::Fixed(cpu, cores))
+    }
+
+    fn load_average(&self) -> io::Result<LoadAverage> {
+        faked(&self.load)
+    }
+
+    fn uptime(&self) -> io::Result<Duration> {
+        faked(&self.uptime)
+    }
+
+    fn boot_time(&self) -> io::Result<i64> {
+        faked(&self.boot_time)
+    }
+
+    fn mounts(&self) -> io::Result<Vec<Filesystem>> {
+        Ok(self.mounts.clone())
+    }
+
+    fn network_stats(&self) -> io::Result<BTreeMap<String, NetworkStats>> {
+        Ok(self.networks.clone())
+    }
+
+    fn block_device_statistics(&self) -> io::Result<BTreeMap<String, BlockDeviceStats>> {
+        Ok(self.block_devices.clone())
+    }
+
+    fn cpu_temp(&self) -> io::Result<f32> {
+        faked(&self.cpu_temp)
+    }
+
+    fn root(&self) -> &Path {
+        &self.root
+    }
+}
+
+/// A capture of another machine's `/proc` and `/sys`, copied below a root
+/// directory, which is read as if it were the live system to reproduce what
+/// StatMonitor reported there.
+///
+/// Filesystem usage is not part of the capture, so there are no mounts.
+/// Rates and CPU usage are derived from counters that do not change, so CPU
+/// usage is the average since boot instead.
+pub struct Replay {
+    root: PathBuf,
+}
+
+impl Replay {
+    pub fn new(root: PathBuf) -> Self {
+        Replay { root }
+    }
+
+    fn read(&self, path: &str) -> io::Result<String> {
+        let path = (self as &dyn Platform).path(path);
+        fs::read_to_string(&path).map_err(|e| in_capture(&path, e))
+    }
+
+    fn meminfo(&self) -> io::Result<BTreeMap<String, ByteSize>> {
+        let meminfo = self.read("/proc/meminfo")?;
+        Ok(meminfo
+            .lines()
+            .filter_map(|line| {
+                let (key, value) = line.split_once(':')?;
+                let kib: u64 = value.split_whitespace().next()?.parse().ok()?;
+                Some((key.to_string(), ByteSize::kib(kib)))
+            })
+            .collect())
+    }
+
+    fn stat(&self, key: &str) -> io::Result<String> {
+        let stat = self.read("/proc/stat")?;
+        stat.lines()
+            .find_map(|line| line.strip_prefix(key)?.strip_prefix(' '))
+            .map(|line| line.trim().to_string())
+            .ok_or_else(|| invalid(&format!("no {} line in /proc/stat", key)))
+    }
+}
+
+impl Platform for Replay {
+    fn memory(&self) -> io::Result<Memory> {
+        Ok(memory(&self.meminfo()?))
+    }
+
+    fn swap(&self) -> io::Result<Swap> {
+        let meminfo = self.meminfo()?;
+        let bytes = |key: &str| meminfo.get(key).map_or(0, |b| b.as_u64());
+        Ok(Swap {
+            used: bytes("SwapTotal").saturating_sub(bytes("SwapFree")),
+            total: bytes("SwapTotal"),
+        })
+    }
+
+    fn cpu_load(&self) -> io::Result<CpuLoad> {
+        let stat = self.read("/proc/stat")?;
+        let mut cpu = None;
+        let mut cores = Vec::new();
+        for line in stat.lines() {
+            let Some((name, times)) = line.split_once(' ') else {
+                continue;
+            };
+            let Some(core) = name.strip_prefix("cpu") else {
+                continue;
+            };
+            // Fields are user, nice, system, idle, iowait and irq, which is
+            // how systemstat reads them.
+            let times: Vec<usize> = times.split_whitespace().take(6).filter_map(|n| n.parse().ok()).collect();
+            let [user, nice, system, idle, other, interrupt] = times[..] else {
+                return Err(invalid("unexpected format of /proc/stat"));
+            };
+            let load = percent(&CpuTime { user, nice, system, interrupt, idle, other }.to_cpuload());
+            if core.is_empty() {
+                cpu = Some(load);
+            } else {
+                cores.push(load);
+            }
+        }
+        let cpu = cpu.ok_or_else(|| invalid("no cpu line in /proc/stat"))?;
+        Ok(CpuLoad::Fixed(cpu, cores))
+    }
+
+    fn load_average(&self) -> io::Result<LoadAverage> {
+        let loadavg = self.read("/proc/loadavg")?;
+        let mut fields = loadavg.split_whitespace().map(str::parse);
+        let mut next = || {
+            fields.next().and_then(Result::ok).ok_or_else(|| invalid("unexpected format of /proc/loadavg"))
+        };
+        Ok(LoadAverage {
+            one: next()?,
+            five: next()?,
+            fifteen: next()?,
+        })
+    }
+
+    fn uptime(&self) -> io::Result<Duration> {
+        let uptime = self.read("/proc/uptime")?;
+        let seconds: f64 = uptime
+            .split_whitespace()
+            .next()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| invalid("unexpected format of /proc/uptime"))?;
+        Ok(Duration::from_secs_f64(seconds))
+    }
+
+    fn boot_time(&self) -> io::Result<i64> {
+        self.stat("btime")?.parse().map_err(|_| invalid("unexpected btime in /proc/stat"))
+    }
+
+    fn mounts(&self) -> io::Result<Vec<Filesystem>> {
+        Ok(Vec::new())
+    }
+
+    fn network_stats(&self) -> io::Result<BTreeMap<String, NetworkStats>> {
+        let mut networks = BTreeMap::new();
+        let dir = self.root.join("sys/class/net");
+        for entry in fs::read_dir(&dir).map_err(|e| in_capture(&dir, e))? {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            let counter = |counter: &str| -> io::Result<u64> {
+                let path = format!("/sys/class/net/{}/statistics/{}", name, counter);
+                self.read(&path)?.trim().parse().map_err(|_| invalid(&format!("unexpected format of {}", path)))
+            };
+            let stats = NetworkStats {
+                rx_bytes: ByteSize::b(counter("rx_bytes")?),
+                tx_bytes: ByteSize::b(counter("tx_bytes")?),
+                rx_packets: counter("rx_packets")?,
+                tx_packets: counter("tx_packets")?,
+                rx_errors: counter("rx_errors")?,
+                tx_errors: counter("tx_errors")?,
+            };
+            networks.insert(name, stats);
+        }
+        Ok(networks)
+    }
+
+    fn block_device_statistics(&self) -> io::Result<BTreeMap<String, BlockDeviceStats>> {
+        let diskstats = self.read("/proc/diskstats")?;
+        let mut devices = BTreeMap::new();
+        for line in diskstats.lines() {
+            // Major and minor number, name and at least 11 counters.
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (Some(name), Some(counters)) = (fields.get(2), fields.get(3..14)) else {
+                return Err(invalid("unexpected format of /proc/diskstats"));
+            };
+            let counters: Vec<usize> = counters.iter().filter_map(|n| n.parse().ok()).collect();
+            if counters.len() < 11 {
+                return Err(invalid("unexpected format of /proc/diskstats"));
+            }
+            let stats = BlockDeviceStats {
+                name: name.to_string(),
+                read_ios: counters[0],
+                read_merges: counters[1],
+                read_sectors: counters[2],
+                read_ticks: counters[3],
+                write_ios: counters[4],
+                write_merges: counters[5],
+                write_sectors: counters[6],
+                write_ticks: counters[7],
+                in_flight: counters[8],
+                io_ticks: counters[9],
+                time_in_queue: counters[10],
+            };
+            devices.entry(name.to_string()).or_insert(stats);
+        }
+        Ok(devices)
+    }
+
+    fn cpu_temp(&self) -> io::Result<f32> {
+        let temp = self
+            .read("/sys/class/thermal/thermal_zone0/temp")
+            .or_else(|_| self.read("/sys/class/hwmon/hwmon0/temp1_input"))?;
+        let millidegrees: f32 = temp.trim().parse().map_err(|_| invalid("unexpected CPU temperature"))?;
+        Ok(millidegrees / 1000.0)
+    }
+
+    fn root(&self) -> &Path {
+        &self.root
+    }
+}
+
+fn faked<T: Clone>(value: &Option<T>) -> io::Result<T> {
+    value.clone().ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "not faked"))
+}
+
+/// Memory usage from the fields of `/proc/meminfo`.
+fn memory(meminfo: &BTreeMap<String, ByteSize>) -> Memory {
+    let meminfo = |key: &str| meminfo.get(key).map(|b| b.as_u64());
+    let total = meminfo("MemTotal").unwrap_or(0);
+    // Kernels before 3.14 have no MemAvailable; counting reclaimable caches
+    // as free, as systemstat does, is the closest estimate.
+    let reclaimable = ["MemFree", "Buffers", "Cached", "SReclaimable"].map(|key| meminfo(key).unwrap_or(0));
+    let free = reclaimable.iter().sum::<u64>().saturating_sub(meminfo("Shmem").unwrap_or(0));
+    let available = meminfo("MemAvailable").unwrap_or(free);
+    Memory {
+        used: total.saturating_sub(available),
+        total,
+        free: meminfo("MemFree").unwrap_or(0),
+        available,
+        buffers: meminfo("Buffers").unwrap_or(0),
+        cached: meminfo("Cached").unwrap_or(0),
+        shared: meminfo("Shmem").unwrap_or(0),
+        slab: meminfo("Slab").unwrap_or(0),
+        dirty: meminfo("Dirty").unwrap_or(0),
+        writeback: meminfo("Writeback").unwrap_or(0),
+    }
+}
+
+/// Names the file of the capture that could not be read, which may simply
+/// be missing from it.
+fn in_capture(path: &Path, e: io::Error) -> io::Error {
+    io::Error::new(e.kind(), format!("{}: {}", path.display(), e))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn percent(load: &CPULoad) -> CPU {
+    CPU {
+        user: load.user * 100.0,
+        nice: load.nice * 100.0,
+        interrupt: load.interrupt * 100.0,
+        system: load.system * 100.0,
+        idle: load.idle * 100.0,
+    }
+}
@@ -0,0 +1,93 @@
+use std::fs;
+use std::io;
+
+use serde::Serialize;
+
+use super::platform::Platform;
+
+/// Pressure stall information from `/proc/pressure`, available on Linux 4.20
+/// and later.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Pressure {
+    pub cpu: Resource,
+    pub memory: Resource,
+    pub io: Resource,
+}
+
+/// How much tasks stalled waiting for one resource.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Resource {
+    /// Time in which at least one task was stalled.
+    pub some: Stall,
+    /// Time in which all non-idle tasks were stalled at once. Kernels before
+    /// 5.13 do not report it for the CPU.
+    pub full: Option<Stall>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Stall {
+    /// Share of the last 10 seconds spent stalled, in percent.
+    pub avg10: f32,
+    /// Share of the last 60 seconds spent stalled, in percent.
+    pub avg60: f32,
+    /// Share of the last 300 seconds spent stalled, in percent.
+    pub avg300: f32,
+    /// Total time spent stalled since boot, in microseconds.
+    pub total: u64,
+}
+
+/// Reads the stall information of every resource, or `None` if the kernel
+/// does not track it: before 4.20, without `CONFIG_PSI` or when booted with
+/// `psi=0`.
+pub fn collect(platform: &dyn Platform) -> io::Result<Option<Pressure>> {
+    let pressure = || -> io::Result<Pressure> {
+        Ok(Pressure {
+            cpu: resource(platform, "cpu")?,
+            memory: resource(platform, "memory")?,
+            io: resource(platform, "io")?,
+        })
+    };
+    match pressure() {
+        Ok(pressure) => Ok(Some(pressure)),
+        // With psi=0 the files exist, but reading them fails with EOPNOTSUPP.
+        Err(e) if matches!(e.kind(), io::ErrorKind::NotFound | io::ErrorKind::Unsupported) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn resource(platform: &dyn Platform, name: &str) -> io::Result<Resource> {
+    let path = format!("/proc/pressure/{}", name);
+    let contents = fs::read_to_string(platform.path(&path))
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))?;
+    let line = |kind: &str| {
+        contents
+            .lines()
+            .find_map(|line| line.strip_prefix(kind)?.strip_prefix(' '))
+            .map(|line| parse(line).ok_or_else(|| invalid(&path)))
+    };
+    Ok(Resource {
+        some: line("some").ok_or_else(|| invalid(&path))??,
+        full: line("full").transpose()?,
+    })
+}
+
+/// Parses the fields of a line such as
+/// `some avg10=0.12 avg60=0.05 avg300=0.01 total=123456`.
+fn parse(line: &str) -> Option<Stall> {
+    let mut stall = Stall::default();
+    for field in line.split_whitespace() {
+        let (key, value) = field.split_once('=')?;
+        match key {
+            "avg10" => stall.avg10 = value.parse().ok()?,
+            "avg60" => stall.avg60 = value.parse().ok()?,
+            "avg300" => stall.avg300 = value.parse().ok()?,
+            "total" => stall.total = value.parse().ok()?,
+            _ => {}
+        }
+    }
+    Some(stall)
+}
+
+fn invalid(path: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("unexpected format of {}", path))
+}
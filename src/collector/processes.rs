@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::platform::Platform;
+
+/// A running process, as read from `/proc/<pid>`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Process {
+    pub pid: u32,
+    /// The full command line, or the process name in brackets for kernel
+    /// threads, which have none.
+    pub command: String,
+    pub user: String,
+    /// Single-letter state, e.g. `R` for running or `S` for sleeping.
+    pub state: String,
+    pub threads: u64,
+    /// Share of the total CPU time of all cores used over the sampling
+    /// window, in percent.
+    pub cpu: f32,
+    /// Resident set size, in bytes.
+    pub rss: u64,
+}
+
+/// CPU time counters taken at the start of the sampling window.
+#[derive(Debug)]
+pub struct Ticks {
+    total: u64,
+    processes: HashMap<u32, u64>,
+}
+
+/// Starts measuring the CPU usage of every process.
+pub fn start(platform: &dyn Platform) -> io::Result<Ticks> {
+    let proc = platform.path("/proc");
+    let mut processes = HashMap::new();
+    for pid in pids(&proc)? {
+        if let Some(ticks) = process_ticks(&proc, pid) {
+            processes.insert(pid, ticks);
+        }
+    }
+    Ok(Ticks {
+        total: total_ticks(&proc)?,
+        processes,
+    })
+}
+
+/// Ends the measurement begun by `start` and lists the processes that are
+/// still running.
+pub fn finish(platform: &dyn Platform, start: Ticks) -> io::Result<Vec<Process>> {
+    let proc = platform.path("/proc");
+    let total = total_ticks(&proc)?.saturating_sub(start.total).max(1);
+    let users = users(&platform.path("/etc/passwd"));
+    let mut processes = Vec::new();
+    for pid in pids(&proc)? {
+        // Processes may exit at any point while they are being read.
+        let Some(ticks) = process_ticks(&proc, pid) else {
+            continue;
+        };
+        let Ok(status) = fs::read_to_string(proc.join(format!("{}/status", pid))) else {
+            continue;
+        };
+        let field = |name: &str| {
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+                .map(str::trim)
+                .unwrap_or_default()
+        };
+        let used = ticks.saturating_sub(start.processes.get(&pid).copied().unwrap_or(ticks));
+        let uid = field("Uid").split_whitespace().next().unwrap_or_default();
+        processes.push(Process {
+            pid,
+            command: command(&proc, pid).unwrap_or_else(|| format!("[{}]", field("Name"))),
+            user: users.get(uid).cloned().unwrap_or_else(|| uid.to_string()),
+            state: field("State").chars().take(1).collect(),
+            threads: field("Threads").parse().unwrap_or(0),
+            cpu: (used as f64 / total as f64 * 100.0) as f32,
+            rss: kilobytes(field("VmRSS")) * 1024,
+        });
+    }
+    Ok(processes)
+}
+
+/// Running processes in ascending order, so that ties in `/processes` are
+/// listed by pid.
+fn pids(proc: &Path) -> io::Result<Vec<u32>> {
+    let mut pids: Vec<u32> = fs::read_dir(proc)?
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+        .collect();
+    pids.sort_unstable();
+    Ok(pids)
+}
+
+/// Time spent by all cores in any state since boot, from the `cpu` line of
+/// `/proc/stat`.
+fn total_ticks(proc: &Path) -> io::Result<u64> {
+    let stat = fs::read_to_string(proc.join("stat"))?;
+    let line = stat
+        .lines()
+        .find(|line| line.starts_with("cpu "))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no cpu line in /proc/stat"))?;
+    Ok(line.split_whitespace().skip(1).filter_map(|n| n.parse::<u64>().ok()).sum())
+}
+
+/// User and system time of a process, from `/proc/<pid>/stat`.
+fn process_ticks(proc: &Path, pid: u32) -> Option<u64> {
+    let stat = fs::read_to_string(proc.join(format!("{}/stat", pid))).ok()?;
+    // The command name may contain spaces and parentheses, so the fields are
+    // counted from the last closing parenthesis; utime and stime are the
+    // 14th and 15th fields of the line.
+    let mut fields = stat.get(stat.rfind(')')? + 2..)?.split_whitespace().skip(11);
+    let utime: u64 = fields.next()?.parse().ok()?;
+    let stime: u64 = fields.next()?.parse().ok()?;
+    Some(utime + stime)
+}
+
+fn command(proc: &Path, pid: u32) -> Option<String> {
+    let cmdline = fs::read(proc.join(format!("{}/cmdline", pid))).ok()?;
+    let args: Vec<String> = cmdline
+        .split(|b| *b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).into_owned())
+        .collect();
+    (!args.is_empty()).then(|| args.join(" "))
+}
+
+/// Parses a `/proc/<pid>/status` size such as `1234 kB`.
+fn kilobytes(value: &str) -> u64 {
+    value.split_whitespace().next().and_then(|n| n.parse().ok()).unwrap_or(0)
+}
+
+/// User names by uid, from `/etc/passwd`.
+fn users(passwd: &Path) -> HashMap<String, String> {
+    let passwd = fs::read_to_string(passwd).unwrap_or_default();
+    passwd
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let uid = fields.nth(1)?;
+            Some((uid.to_string(), name.to_string()))
+        })
+        .collect()
+}
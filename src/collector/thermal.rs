@@ -0,0 +1,112 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use super::platform::Platform;
+
+/// Temperatures in degrees Celsius.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Thermal {
+    /// CPU package temperature; `None` if the system does not expose one.
+    pub cpu: Option<f32>,
+    pub zones: Vec<Zone>,
+    pub sensors: Vec<Sensor>,
+}
+
+/// A thermal zone from `/sys/class/thermal`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Zone {
+    /// Directory name, e.g. `thermal_zone0`.
+    pub name: String,
+    /// What the zone measures, e.g. `x86_pkg_temp`.
+    pub kind: String,
+    pub temperature: f32,
+}
+
+/// A temperature input of a hardware monitoring chip from `/sys/class/hwmon`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Sensor {
+    /// Directory name, e.g. `hwmon1`, which tells apart identical chips.
+    pub device: String,
+    /// Driver name of the chip, e.g. `coretemp`.
+    pub chip: String,
+    /// The sensor's label, e.g. `Core 0`, or its input name if unlabelled.
+    pub label: String,
+    pub temperature: f32,
+}
+
+/// Reads every temperature the kernel exposes. Machines without sensors,
+/// such as most virtual machines, simply report none.
+pub fn collect(platform: &dyn Platform) -> io::Result<Thermal> {
+    Ok(Thermal {
+        cpu: platform.cpu_temp().ok(),
+        zones: zones(&platform.path("/sys/class/thermal"))?,
+        sensors: sensors(&platform.path("/sys/class/hwmon"))?,
+    })
+}
+
+fn zones(root: &Path) -> io::Result<Vec<Zone>> {
+    let mut zones = Vec::new();
+    for dir in entries(root)? {
+        let name = dir.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        if !name.starts_with("thermal_zone") {
+            continue;
+        }
+        let Some(temperature) = millidegrees(&dir.join("temp")) else {
+            continue;
+        };
+        zones.push(Zone {
+            name,
+            kind: read_trimmed(&dir.join("type")).unwrap_or_default(),
+            temperature,
+        });
+    }
+    Ok(zones)
+}
+
+fn sensors(root: &Path) -> io::Result<Vec<Sensor>> {
+    let mut sensors = Vec::new();
+    for dir in entries(root)? {
+        let device = dir.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let chip = read_trimmed(&dir.join("name")).unwrap_or_default();
+        for input in entries(&dir)? {
+            let file = input.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            let Some(sensor) = file.strip_suffix("_input").filter(|s| s.starts_with("temp")) else {
+                continue;
+            };
+            let Some(temperature) = millidegrees(&input) else {
+                continue;
+            };
+            let label = read_trimmed(&dir.join(format!("{}_label", sensor)));
+            sensors.push(Sensor {
+                device: device.clone(),
+                chip: chip.clone(),
+                label: label.unwrap_or_else(|| sensor.to_string()),
+                temperature,
+            });
+        }
+    }
+    Ok(sensors)
+}
+
+/// Sorted entries of a directory, none if it does not exist.
+fn entries(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut entries = match fs::read_dir(dir) {
+        Ok(entries) => entries.map(|e| e.map(|e| e.path())).collect::<io::Result<Vec<_>>>()?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    entries.sort();
+    Ok(entries)
+}
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+/// Reads a sysfs temperature, which is in thousandths of a degree.
+fn millidegrees(path: &Path) -> Option<f32> {
+    Some(read_trimmed(path)?.parse::<f32>().ok()? / 1000.0)
+}
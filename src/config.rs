@@ -0,0 +1,252 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::{env, fs, io};
+
+use serde::Deserialize;
+use tokio::time::Duration;
+
+use crate::alerts::{self, AlertConfig};
+use crate::history;
+
+/// Runtime configuration, read from an optional TOML file and then
+/// overridden by environment variables.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Address the HTTP server binds to.
+    pub bind: IpAddr,
+    pub port: u16,
+    /// Seconds between two samples taken by the collector.
+    pub sample_interval: u64,
+    /// Seconds clients and proxies may cache a response for.
+    pub cache_ttl: u64,
+    /// Number of samples kept for `/history`.
+    pub history_size: usize,
+    /// Report memory and CPU usage of our own cgroup against its limits
+    /// rather than those of the host.
+    pub container: bool,
+    /// Read `/proc` and `/sys` below this directory, a capture of another
+    /// machine's, instead of the live system.
+    pub replay: Option<PathBuf>,
+    pub collectors: Collectors,
+    pub formats: Formats,
+    pub disks: DiskFilter,
+    pub cgroups: CgroupConfig,
+    pub alerts: AlertConfig,
+}
+
+/// Which collectors run, by name, e.g. `cpu = false`. Collectors that are
+/// not mentioned run unless they are off by default.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct Collectors(BTreeMap<String, bool>);
+
+impl Collectors {
+    /// Whether the collector `name` runs, `default` if it is not mentioned.
+    pub fn enabled(&self, name: &str, default: bool) -> bool {
+        self.0.get(name).copied().unwrap_or(default)
+    }
+
+    /// Names of the collectors mentioned.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.0.keys().map(String::as_str)
+    }
+}
+
+/// Filesystem types reported by the disk collector.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiskFilter {
+    /// If not empty, only these filesystem types are reported.
+    pub include_fs_types: Vec<String>,
+    /// Filesystem types that are never reported.
+    pub exclude_fs_types: Vec<String>,
+}
+
+impl DiskFilter {
+    pub fn allows(&self, fs_type: &str) -> bool {
+        (self.include_fs_types.is_empty() || self.include_fs_types.iter().any(|t| t == fs_type))
+            && !self.exclude_fs_types.iter().any(|t| t == fs_type)
+    }
+}
+
+/// Which cgroups the cgroup collector reports.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CgroupConfig {
+    /// Mount point of the cgroup v2 hierarchy.
+    pub root: PathBuf,
+    /// How many levels below the root are walked; 0 reports only the root.
+    pub depth: usize,
+    /// If not empty, only groups whose path contains one of these are
+    /// reported. Groups below a filtered out group are still walked.
+    pub include: Vec<String>,
+}
+
+/// Which output formats are served.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Formats {
+    /// The JSON snapshot on `/` and the JSON `/history`.
+    pub json: bool,
+    /// The Prometheus exposition on `/metrics`.
+    pub prometheus: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            bind: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            port: 8080,
+            sample_interval: 5,
+            cache_ttl: 5,
+            history_size: history::DEFAULT_SIZE,
+            container: false,
+            replay: None,
+            collectors: Collectors::default(),
+            formats: Formats::default(),
+            disks: DiskFilter::default(),
+            cgroups: CgroupConfig::default(),
+            alerts: AlertConfig::default(),
+        }
+    }
+}
+
+impl Default for DiskFilter {
+    fn default() -> Self {
+        // Pseudo and in-memory filesystems that never fill up a disk.
+        let exclude = [
+            "autofs", "binfmt_misc", "bpf", "cgroup", "cgroup2", "configfs", "debugfs", "devpts",
+            "devtmpfs", "fusectl", "hugetlbfs", "mqueue", "nsfs", "overlay", "proc", "pstore",
+            "ramfs", "securityfs", "squashfs", "sysfs", "tmpfs", "tracefs",
+        ];
+        DiskFilter {
+            include_fs_types: Vec::new(),
+            exclude_fs_types: exclude.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+}
+
+impl Default for CgroupConfig {
+    fn default() -> Self {
+        CgroupConfig {
+            root: PathBuf::from("/sys/fs/cgroup"),
+            depth: 2,
+            include: Vec::new(),
+        }
+    }
+}
+
+impl Default for Formats {
+    fn default() -> Self {
+        Formats {
+            json: true,
+            prometheus: true,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Env(&'static str, String),
+    Invalid(&'static str),
+    Rule(String, &'static str),
+    Webhook(String, &'static str),
+    UnknownCollector(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, e) => write!(f, "cannot read {}: {}", path.display(), e),
+            ConfigError::Parse(path, e) => write!(f, "invalid config file {}: {}", path.display(), e),
+            ConfigError::Env(var, value) => write!(f, "invalid value {:?} for {}", value, var),
+            ConfigError::Invalid(reason) => write!(f, "invalid config: {}", reason),
+            ConfigError::Rule(name, reason) => write!(f, "invalid alert rule {:?}: {}", name, reason),
+            ConfigError::Webhook(url, reason) => write!(f, "invalid webhook {:?}: {}", url, reason),
+            ConfigError::UnknownCollector(name) => write!(f, "unknown collector {:?} in [collectors]", name),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Loads the config file at `path`, if any, applies environment overrides
+    /// and validates the result.
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let mut config = match path {
+            Some(path) => {
+                let text = fs::read_to_string(path).map_err(|e| ConfigError::Read(path.into(), e))?;
+                toml::from_str(&text).map_err(|e| ConfigError::Parse(path.into(), e))?
+            }
+            None => Config::default(),
+        };
+
+        override_from_env("BIND_ADDRESS", &mut config.bind)?;
+        override_from_env("PORT", &mut config.port)?;
+        override_from_env("SAMPLE_INTERVAL", &mut config.sample_interval)?;
+        override_from_env("CACHE_TTL", &mut config.cache_ttl)?;
+        override_from_env("HISTORY_SIZE", &mut config.history_size)?;
+        override_from_env("CONTAINER_MODE", &mut config.container)?;
+        if let Ok(root) = env::var("REPLAY_ROOT") {
+            config.replay = Some(root.into());
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks the settings for values that would fail or silently break
+    /// StatMonitor at runtime.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if !(1..=86_400).contains(&self.sample_interval) {
+            return Err(ConfigError::Invalid("sample_interval must be between 1 second and a day"));
+        }
+        if self.history_size > history::MAX_SIZE {
+            return Err(ConfigError::Invalid("history_size must be at most 100000"));
+        }
+        if self.disks.include_fs_types.iter().any(|t| self.disks.exclude_fs_types.contains(t)) {
+            return Err(ConfigError::Invalid(
+                "a filesystem type in [disks] include_fs_types is also excluded and would never be reported",
+            ));
+        }
+        if !self.cgroups.root.is_absolute() {
+            return Err(ConfigError::Invalid("[cgroups] root must be an absolute path"));
+        }
+        if self.replay.as_ref().is_some_and(|root| !root.is_dir()) {
+            return Err(ConfigError::Invalid("replay must be a directory"));
+        }
+        for (i, rule) in self.alerts.rules.iter().enumerate() {
+            rule.validate().map_err(|reason| ConfigError::Rule(rule.name.clone(), reason))?;
+            // Silences and `/alerts` tell rules apart by name.
+            if self.alerts.rules[..i].iter().any(|other| other.name == rule.name) {
+                return Err(ConfigError::Rule(rule.name.clone(), "another rule has the same name"));
+            }
+        }
+        for url in &self.alerts.webhooks {
+            alerts::validate_webhook(url).map_err(|reason| ConfigError::Webhook(url.clone(), reason))?;
+        }
+        Ok(())
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        SocketAddr::new(self.bind, self.port)
+    }
+
+    pub fn sample_interval(&self) -> Duration {
+        Duration::from_secs(self.sample_interval)
+    }
+}
+
+fn override_from_env<T: FromStr>(var: &'static str, field: &mut T) -> Result<(), ConfigError> {
+    if let Ok(value) = env::var(var) {
+        *field = value.parse().map_err(|_| ConfigError::Env(var, value))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,88 @@
+use std::io;
+
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Serialize;
+
+/// A collector that failed during a sample. The other collectors of the same
+/// sample still report.
+#[derive(Debug, Clone, Serialize)]
+pub struct CollectorError {
+    pub collector: &'static str,
+    /// Machine-readable reason: `not_found`, `permission_denied`,
+    /// `unsupported` or `io_error`.
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl CollectorError {
+    pub fn new(collector: &'static str, e: &io::Error) -> Self {
+        let code = match e.kind() {
+            io::ErrorKind::NotFound => "not_found",
+            io::ErrorKind::PermissionDenied => "permission_denied",
+            io::ErrorKind::Unsupported => "unsupported",
+            _ => "io_error",
+        };
+        CollectorError {
+            collector,
+            code,
+            message: e.to_string(),
+        }
+    }
+}
+
+/// Errors returned by the HTTP handlers as `{"error": {"code", "message"}}`.
+#[derive(Debug)]
+pub enum ApiError {
+    /// The collector has not finished its first sample yet.
+    NotReady,
+    /// The latest sample is older than it should be; the collector is stuck.
+    Stale(i64),
+    BadRequest(&'static str),
+    NotFound(&'static str),
+    /// The collector needed to answer failed in the latest sample.
+    CollectorFailed(CollectorError),
+}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::NotReady | ApiError::Stale(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::CollectorFailed(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::NotReady => "not_ready",
+            ApiError::Stale(_) => "stale",
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::CollectorFailed(_) => "collector_failed",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            ApiError::NotReady => "no sample has been collected yet".to_string(),
+            ApiError::Stale(age) => format!("the latest sample is {} seconds old", age),
+            ApiError::BadRequest(reason) | ApiError::NotFound(reason) => reason.to_string(),
+            ApiError::CollectorFailed(e) => format!("{} collector failed: {}", e.collector, e.message),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = serde_json::json!({
+            "error": {
+                "code": self.code(),
+                "message": self.message(),
+            }
+        });
+        (self.status(), Json(body)).into_response()
+    }
+}
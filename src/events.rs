@@ -0,0 +1,94 @@
+use std::convert::Infallible;
+
+use axum::extract::State;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use futures_util::stream::{self, Stream};
+use serde::Deserialize;
+use tokio::time::{sleep_until, Duration, Instant};
+
+use crate::collector::CollectorInfo;
+use crate::error::ApiError;
+use crate::extract::ApiQuery;
+use crate::{snapshot_json, AppState, Shared};
+
+/// Keys of the snapshot that are sent regardless of the `metrics` filter.
+const ALWAYS_SENT: [&str; 2] = ["errors", "last_updated"];
+
+/// Largest `interval` a client can ask for.
+const MAX_INTERVAL: Duration = Duration::from_secs(3600);
+
+#[derive(Debug, Deserialize)]
+pub struct EventsQuery {
+    /// Minimum number of seconds between two events sent to this client.
+    interval: Option<f64>,
+    /// Comma-separated keys of the snapshot to send, e.g. `cpu,memory`.
+    metrics: Option<String>,
+}
+
+/// Streams every new snapshot as a `snapshot` server-sent event, starting
+/// with the current one. Clients that fall behind skip to the latest
+/// snapshot instead of holding up the collector.
+pub async fn events(
+    State(shared): State<Shared>,
+    ApiQuery(query): ApiQuery<EventsQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let min_interval = match query.interval.map(Duration::try_from_secs_f64) {
+        Some(Ok(interval)) if interval <= MAX_INTERVAL => interval,
+        Some(_) => return Err(ApiError::BadRequest("interval must be between 0 and 3600 seconds")),
+        None => Duration::ZERO,
+    };
+    let metrics = match query.metrics {
+        Some(metrics) => Some(parse_metrics(&shared.collectors, &metrics)?),
+        None => None,
+    };
+
+    let mut updates = shared.updates.clone();
+    // The current snapshot goes out right away, unless there is none yet.
+    let pending = updates.borrow_and_update().last_updated != 0;
+
+    let events = stream::unfold((updates, pending, None), move |(mut updates, pending, sent_at)| {
+        let metrics = metrics.clone();
+        async move {
+            if let Some(sent_at) = sent_at {
+                sleep_until(sent_at + min_interval).await;
+            }
+            if !pending {
+                // The collector has stopped when the sender is dropped.
+                updates.changed().await.ok()?;
+            }
+            let state = updates.borrow_and_update().clone();
+            let event = Event::default()
+                .event("snapshot")
+                .json_data(filter(&state, metrics.as_deref()))
+                .unwrap();
+            Some((Ok(event), (updates, false, Some(Instant::now()))))
+        }
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+/// Splits and validates the `metrics` query parameter.
+fn parse_metrics(collectors: &[CollectorInfo], metrics: &str) -> Result<Vec<String>, ApiError> {
+    let metrics: Vec<String> = metrics.split(',').map(|m| m.trim().to_string()).collect();
+    if !metrics.iter().all(|m| is_metric(collectors, m)) {
+        return Err(ApiError::BadRequest("unknown metric in metrics"));
+    }
+    Ok(metrics)
+}
+
+/// Whether `name` is a key of the snapshot JSON that can be filtered on,
+/// either a built-in one or one filled in by one of `collectors`.
+pub fn is_metric(collectors: &[CollectorInfo], name: &str) -> bool {
+    !ALWAYS_SENT.contains(&name)
+        && (snapshot_json(&AppState::default()).get(name).is_some()
+            || collectors.iter().any(|c| c.schema.keys.contains(&name)))
+}
+
+/// The snapshot JSON, limited to `metrics` if given.
+pub fn filter(state: &AppState, metrics: Option<&[String]>) -> serde_json::Value {
+    let mut json = snapshot_json(state);
+    if let (Some(metrics), Some(object)) = (metrics, json.as_object_mut()) {
+        object.retain(|key, _| ALWAYS_SENT.contains(&key.as_str()) || metrics.contains(key));
+    }
+    json
+}
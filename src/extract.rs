@@ -0,0 +1,61 @@
+//! Extractors that reject malformed requests with an `ApiError`, so that
+//! clients get the same JSON error body as from the handlers themselves
+//! rather than axum's plain-text rejections.
+
+use axum::async_trait;
+use axum::extract::rejection::JsonRejection;
+use axum::extract::{FromRequest, FromRequestParts, Query};
+use axum::http::request::Parts;
+use axum::http::Request;
+use axum::Json;
+use serde::de::DeserializeOwned;
+
+use crate::error::ApiError;
+
+/// `Query`, failing with a 400 `bad_request`.
+pub struct ApiQuery<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for ApiQuery<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, ApiError> {
+        match Query::from_request_parts(parts, state).await {
+            Ok(Query(query)) => Ok(ApiQuery(query)),
+            Err(e) => {
+                log::debug!("rejected query: {}", e);
+                Err(ApiError::BadRequest("invalid query parameters"))
+            }
+        }
+    }
+}
+
+/// `Json`, failing with a 400 `bad_request`.
+pub struct ApiJson<T>(pub T);
+
+#[async_trait]
+impl<T, S, B> FromRequest<S, B> for ApiJson<T>
+where
+    Json<T>: FromRequest<S, B, Rejection = JsonRejection>,
+    S: Send + Sync,
+    B: Send + 'static,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(request: Request<B>, state: &S) -> Result<Self, ApiError> {
+        match Json::from_request(request, state).await {
+            Ok(Json(body)) => Ok(ApiJson(body)),
+            Err(JsonRejection::MissingJsonContentType(_)) => {
+                Err(ApiError::BadRequest("the body must be JSON with content-type application/json"))
+            }
+            Err(e) => {
+                log::debug!("rejected body: {}", e);
+                Err(ApiError::BadRequest("invalid request body"))
+            }
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::collections::VecDeque;
+
+use serde::Serialize;
+
+use crate::{AppState, CPU, Memory, Swap};
+
+/// Number of samples kept by default: one hour at the default five-second
+/// sampling interval.
+pub const DEFAULT_SIZE: usize = 720;
+
+/// Most samples that can be kept, about 14 MB: over a day at a one-second
+/// sampling interval.
+pub const MAX_SIZE: usize = 100_000;
+
+/// A single entry in the history buffer.
+///
+/// Per-core CPU usage is deliberately left out so that every entry has the
+/// same fixed size regardless of the host, 144 bytes on 64-bit targets.
+#[derive(Debug, Clone, Serialize)]
+pub struct Point {
+    pub timestamp: i64,
+    pub cpu: Option<CPU>,
+    pub memory: Option<Memory>,
+    pub swap: Option<Swap>,
+}
+
+/// Fixed-capacity ring buffer of past snapshots, oldest first.
+#[derive(Debug)]
+pub struct History {
+    capacity: usize,
+    points: VecDeque<Point>,
+}
+
+impl History {
+    /// Creates an empty history holding at most `capacity` samples. The
+    /// whole buffer is allocated up front.
+    pub fn new(capacity: usize) -> Self {
+        History {
+            capacity,
+            points: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Records a snapshot, evicting the oldest sample once the buffer is full.
+    pub fn push(&mut self, state: &AppState) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.points.len() == self.capacity {
+            self.points.pop_front();
+        }
+        self.points.push_back(Point {
+            timestamp: state.last_updated,
+            cpu: state.cpu_usage.clone(),
+            memory: state.memory_usage.clone(),
+            swap: state.swap_usage.clone(),
+        });
+    }
+
+    /// Timestamps of the oldest and newest samples, if any.
+    pub fn bounds(&self) -> Option<(i64, i64)> {
+        Some((self.points.front()?.timestamp, self.points.back()?.timestamp))
+    }
+
+    /// Returns the samples taken between `from` and `to` inclusive. With a
+    /// `step`, consecutive samples are at least `step` seconds apart.
+    pub fn range(&self, from: i64, to: i64, step: Option<i64>) -> Vec<Point> {
+        let mut out = Vec::new();
+        let mut next = from;
+        for point in self.points.iter() {
+            if point.timestamp < next || point.timestamp > to {
+                continue;
+            }
+            out.push(point.clone());
+            if let Some(step) = step {
+                next = point.timestamp.saturating_add(step);
+            }
+        }
+        out
+    }
+}
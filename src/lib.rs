@@ -0,0 +1,348 @@
+//! StatMonitor's collectors and HTTP API, for embedding in other services.
+//!
+//! [`collect`] takes a single snapshot of the system. To serve snapshots
+//! continuously, [`start`] the collector and mount the [`router`] in an axum
+//! app:
+//!
+//! ```no_run
+//! use axum::Router;
+//! use stat_monitor::config::Config;
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let shared = stat_monitor::start(Config::default())?;
+//! let app: Router = Router::new().nest("/stats", stat_monitor::router(shared));
+//! axum::Server::bind(&"0.0.0.0:3000".parse()?)
+//!     .serve(app.into_make_service())
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod alerts;
+pub mod collector;
+pub mod config;
+pub mod error;
+mod events;
+mod extract;
+pub mod history;
+mod processes;
+pub mod prometheus;
+pub mod table;
+mod ws;
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use axum::Json;
+use axum::extract::State;
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::{Router, routing::{delete, get, post}};
+use tokio::sync::{watch, RwLock};
+use serde::{Deserialize, Serialize};
+
+use collector::cgroups::Cgroup;
+use collector::{CollectorInfo, Registry, Sampler};
+use collector::diskio::Device;
+use collector::disks::Disk;
+use collector::network::Interface;
+use collector::pressure::Pressure;
+use collector::processes::Process;
+use collector::thermal::Thermal;
+use alerts::AlertEngine;
+use config::{Config, ConfigError};
+use error::{ApiError, CollectorError};
+use extract::ApiQuery;
+use history::History;
+
+/// A snapshot older than this many sampling intervals is reported as stale.
+const STALE_AFTER_INTERVALS: i64 = 3;
+
+/// Collectors whose failure makes `/` a 500 and `once` exit with status 1;
+/// without them a snapshot is of little use.
+const CORE_COLLECTORS: [&str; 3] = ["cpu", "memory", "swap"];
+
+/// State shared between the collector and the handlers.
+#[derive(Clone)]
+pub struct Shared {
+    config: Arc<Config>,
+    state: Arc<RwLock<AppState>>,
+    history: Arc<RwLock<History>>,
+    /// Receives every snapshot as the collector publishes it.
+    updates: watch::Receiver<Arc<AppState>>,
+    alerts: Arc<RwLock<AlertEngine>>,
+    /// The enabled collectors.
+    collectors: Arc<[CollectorInfo]>,
+}
+
+impl Shared {
+    /// State with no sample yet, and the sender the collector publishes
+    /// snapshots to.
+    pub fn new(config: Config, collectors: Vec<CollectorInfo>) -> (Self, watch::Sender<Arc<AppState>>) {
+        let (publisher, updates) = watch::channel(Arc::new(AppState::default()));
+        let shared = Shared {
+            state: Arc::new(RwLock::new(AppState::default())),
+            history: Arc::new(RwLock::new(History::new(config.history_size))),
+            updates,
+            alerts: Arc::new(RwLock::new(AlertEngine::new(config.alerts.rules.clone()))),
+            collectors: collectors.into(),
+            config: Arc::new(config),
+        };
+        (shared, publisher)
+    }
+
+    pub fn config(&self) -> &Arc<Config> {
+        &self.config
+    }
+
+    /// Receives every snapshot as the collector publishes it, starting with
+    /// the latest one.
+    pub fn subscribe(&self) -> watch::Receiver<Arc<AppState>> {
+        self.updates.clone()
+    }
+
+    /// Fails if the collector has not produced a sample recently enough for
+    /// `state` to be served.
+    fn check_fresh(&self, state: &AppState) -> Result<(), ApiError> {
+        if state.last_updated == 0 {
+            return Err(ApiError::NotReady);
+        }
+        let age = chrono::Utc::now().timestamp() - state.last_updated;
+        // One extra interval covers the time a sample itself takes.
+        let max_age = (STALE_AFTER_INTERVALS + 1).saturating_mul(self.config.sample_interval as i64);
+        if age > max_age {
+            return Err(ApiError::Stale(age));
+        }
+        Ok(())
+    }
+}
+
+/// The latest snapshot. Metrics of disabled and failed collectors are left
+/// empty.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AppState {
+    pub cpu_usage: Option<CPU>,
+    pub cpu_cores: Vec<CPU>,
+    pub memory_usage: Option<Memory>,
+    pub swap_usage: Option<Swap>,
+    pub pressure: Option<Pressure>,
+    pub cgroups: Option<Vec<Cgroup>>,
+    pub disks: Option<Vec<Disk>>,
+    pub disk_io: Option<Vec<Device>>,
+    pub network: Option<Vec<Interface>>,
+    pub load_average: Option<LoadAverage>,
+    /// Seconds since boot.
+    pub uptime: Option<u64>,
+    /// Unix time the system booted at.
+    pub boot_time: Option<i64>,
+    pub thermal: Option<Thermal>,
+    /// Metrics of collectors without a field of their own, by snapshot key.
+    pub extra: BTreeMap<&'static str, serde_json::Value>,
+    /// Every process, served by `/processes` rather than with the snapshot.
+    pub processes: Option<Vec<Process>>,
+    /// Collectors that ran for this snapshot.
+    pub collectors: Vec<&'static str>,
+    /// Collectors that failed for this snapshot.
+    pub errors: Vec<CollectorError>,
+    pub last_updated: i64,
+}
+
+impl AppState {
+    /// Whether one of the collectors the snapshot is of little use without,
+    /// `cpu`, `memory` or `swap`, failed.
+    pub fn core_failed(&self) -> bool {
+        self.errors.iter().any(|e| CORE_COLLECTORS.contains(&e.collector))
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LoadAverage {
+    pub one: f32,
+    pub five: f32,
+    pub fifteen: f32,
+}
+
+/// Memory usage in bytes, from `/proc/meminfo`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Memory {
+    /// Memory that cannot be reclaimed, `total - available`, as reported by
+    /// `free`.
+    pub used: u64,
+    pub total: u64,
+    /// Memory not used for anything, not even caches.
+    pub free: u64,
+    /// Estimate of the memory available to new programs without swapping.
+    pub available: u64,
+    pub buffers: u64,
+    /// Page cache, excluding swap cache.
+    pub cached: u64,
+    /// Memory used by tmpfs and shared memory.
+    pub shared: u64,
+    /// Kernel slab allocations.
+    pub slab: u64,
+    /// Memory waiting to be written back to disk.
+    pub dirty: u64,
+    /// Memory being written back to disk.
+    pub writeback: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Swap {
+    pub used: u64,
+    pub total: u64,
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CPU {
+    pub user: f32,
+    pub nice: f32,
+    pub interrupt: f32,
+    pub system: f32,
+    pub idle: f32,
+}
+
+/// Takes a single snapshot of the live system with the default collectors,
+/// which takes about a second while CPU usage is measured.
+pub async fn collect() -> AppState {
+    let config = Arc::new(Config::default());
+    let registry = Registry::builtin()
+        .configure(&config.collectors)
+        .expect("the default config names no collectors");
+    Sampler::new(config, registry).sample().await
+}
+
+/// Spawns the collector and alert tasks for `config` on the current Tokio
+/// runtime, returning the state they keep up to date for the [`router`].
+/// Fails if the config is invalid, see [`Config::validate`], or if
+/// `[collectors]` names a collector that does not exist.
+pub fn start(config: Config) -> Result<Shared, ConfigError> {
+    config.validate()?;
+    let registry = Registry::builtin().configure(&config.collectors)?;
+    let (shared, publisher) = Shared::new(config, registry.info());
+    let sampler = Sampler::new(shared.config.clone(), registry);
+    collector::spawn(shared.clone(), sampler, publisher);
+    alerts::spawn(shared.clone());
+    Ok(shared)
+}
+
+/// The HTTP API, serving whatever the collector publishes to `shared`.
+pub fn router(shared: Shared) -> Router {
+    let mut app = Router::new()
+        .route("/alerts", get(alerts::list))
+        .route("/alerts/silences", post(alerts::silence))
+        .route("/alerts/silences/:id", delete(alerts::unsilence))
+        .route("/collectors", get(collectors));
+    if shared.config.formats.json {
+        app = app
+            .route("/", get(root))
+            .route("/history", get(history))
+            .route("/events", get(events::events))
+            .route("/ws", get(ws::ws));
+        if shared.collectors.iter().any(|c| c.name == "processes") {
+            app = app.route("/processes", get(processes::processes));
+        }
+    }
+    if shared.config.formats.prometheus {
+        app = app.route("/metrics", get(metrics));
+    }
+    app.with_state(shared)
+}
+
+/// `Cache-Control` header telling clients how long a snapshot stays fresh.
+fn cache_control(config: &Config) -> (header::HeaderName, HeaderValue) {
+    let value = format!("max-age={}", config.cache_ttl);
+    (header::CACHE_CONTROL, HeaderValue::from_str(&value).unwrap())
+}
+
+/// Serves the latest snapshot. If some collectors failed, the others still
+/// report and the failures are listed under `errors`; the response is a 500
+/// only if one of the core collectors failed.
+async fn root(State(shared): State<Shared>) -> Result<Response, ApiError> {
+    let state = shared.state.read().await;
+    shared.check_fresh(&state)?;
+    let status = if state.core_failed() {
+        StatusCode::INTERNAL_SERVER_ERROR
+    } else {
+        StatusCode::OK
+    };
+    let body = snapshot_json(&state);
+    Ok((status, [cache_control(&shared.config)], Json(body)).into_response())
+}
+
+/// The JSON representation of a snapshot served by `/` and `/events`.
+pub fn snapshot_json(state: &AppState) -> serde_json::Value {
+    let mut json = serde_json::json!({
+        "cpu": state.cpu_usage,
+        "cores": state.cpu_cores,
+        "memory": state.memory_usage,
+        "swap": state.swap_usage,
+        "pressure": state.pressure,
+        "cgroups": state.cgroups,
+        "disks": state.disks,
+        "diskio": state.disk_io,
+        "network": state.network,
+        "load": state.load_average,
+        "uptime": state.uptime,
+        "boot_time": state.boot_time,
+        "thermal": state.thermal,
+        "errors": state.errors,
+        "last_updated": state.last_updated,
+    });
+    if let Some(object) = json.as_object_mut() {
+        for (key, value) in &state.extra {
+            object.insert(key.to_string(), value.clone());
+        }
+    }
+    json
+}
+
+/// Lists the enabled collectors and the snapshot keys they fill in.
+async fn collectors(State(shared): State<Shared>) -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "collectors": &*shared.collectors }))
+}
+
+/// Serves the latest snapshot to Prometheus. Failed collectors are reported
+/// through `statmonitor_collector_success` rather than the status code, so
+/// that the metrics of the working ones are still scraped.
+async fn metrics(State(shared): State<Shared>) -> Result<impl IntoResponse, ApiError> {
+    let state = shared.state.read().await;
+    shared.check_fresh(&state)?;
+    let body = prometheus::render(&state);
+    Ok((
+        [
+            (header::CONTENT_TYPE, HeaderValue::from_static(prometheus::CONTENT_TYPE)),
+            cache_control(&shared.config),
+        ],
+        body,
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+struct HistoryQuery {
+    from: Option<i64>,
+    to: Option<i64>,
+    step: Option<i64>,
+}
+
+async fn history(
+    State(shared): State<Shared>,
+    ApiQuery(query): ApiQuery<HistoryQuery>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    if query.step.is_some_and(|step| step <= 0) {
+        return Err(ApiError::BadRequest("step must be positive"));
+    }
+    let history = shared.history.read().await;
+    let (oldest, newest) = history.bounds().unwrap_or((0, 0));
+    let from = query.from.unwrap_or(oldest);
+    let to = query.to.unwrap_or(newest);
+    if from > to {
+        return Err(ApiError::BadRequest("from must not be after to"));
+    }
+    Ok(serde_json::json!({
+        "from": from,
+        "to": to,
+        "step": query.step,
+        "points": history.range(from, to, query.step),
+    }).into())
+}
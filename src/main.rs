@@ -1,42 +1,65 @@
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
 use std::sync::Arc;
-use std::{net::SocketAddr, env};
-
-use axum::Json;
-use axum::extract::State;
-use axum::{Router, routing::get};
-use tokio::{sync::Mutex, time::{Duration, sleep}};
-use serde::Serialize;
-use serde_json::from_str;
-use systemstat::{saturating_sub_bytes, Platform, System};
-
-#[derive(Debug, Clone, Serialize)]
-struct AppState {
-    cpu_usage: CPU,
-    memory_usage: Memory,
-    swap_usage: Memory,
-    last_updated: i64,
+
+use clap::{Parser, Subcommand, ValueEnum};
+use tokio::time::{interval, Duration, MissedTickBehavior};
+
+use stat_monitor::collector::{Registry, Sampler};
+use stat_monitor::config::Config;
+
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Args {
+    /// Path to a TOML config file.
+    #[arg(short, long, env = "STATMONITOR_CONFIG", global = true)]
+    config: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-#[derive(Debug, Clone, Serialize)]
-struct Memory {
-    used: u64,
-    total: u64,
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Run the HTTP server; the default without a subcommand.
+    Serve,
+    /// Print a single snapshot and exit, with status 1 if CPU, memory or swap
+    /// could not be read.
+    Once {
+        #[arg(short, long, value_enum, default_value_t = Format::Json)]
+        format: Format,
+    },
+    /// Print a table of the system that refreshes until interrupted.
+    Watch {
+        /// Seconds between refreshes, at most a day; defaults to
+        /// `sample_interval`.
+        #[arg(short = 'n', long, value_parser = clap::value_parser!(u64).range(1..=86_400))]
+        interval: Option<u64>,
+    },
+    /// Validate the config and list what it enables, then exit.
+    CheckConfig,
+    /// Print the version and exit.
+    Version,
 }
 
-#[derive(Debug, Clone, Serialize)]
-struct CPU {
-    user: f32,
-    nice: f32,
-    interrupt: f32,
-    system: f32,
-    idle: f32,
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Format {
+    Json,
+    Table,
 }
 
 #[tokio::main]
 async fn main() {
+    // A missing .env file is fine; everything has a default.
+    dotenvy::dotenv().ok();
+    let args = Args::parse();
+    let command = args.command.unwrap_or(Command::Serve);
     env_logger::builder()
         .filter_module("stat_monitor", {
-            if cfg!(debug_assertions) {
+            if !matches!(command, Command::Serve) {
+                // Leave the terminal to the output.
+                log::LevelFilter::Warn
+            } else if cfg!(debug_assertions) {
                 log::LevelFilter::Trace
             } else {
                 log::LevelFilter::Info
@@ -44,109 +67,92 @@ async fn main() {
         })
         .init();
 
-    let shared_state = Arc::new(Mutex::new(AppState {
-        cpu_usage: CPU {
-            user: 0.0,
-            nice: 0.0,
-            interrupt: 0.0,
-            system: 0.0,
-            idle: 0.0,
-        },
-        memory_usage: Memory {
-            used: 0,
-            total: 0,
-        },
-        swap_usage: Memory {
-            used: 0,
-            total: 0,
-        },
-        last_updated: 0,
-    }));
-
-    let app = Router::new()
-        .route("/", get(root))
-        .with_state(shared_state);
-
-    let addr = SocketAddr::from((
-        [0, 0, 0, 0],
-        from_str::<u16>(
-            env::var("PORT")
-                .unwrap_or("8080".to_string())
-                .as_str(),
-        )
-        .unwrap(),
-    ));
+    let path = args.config;
+    match command {
+        Command::Serve => serve(load(path.as_deref())).await,
+        Command::Once { format } => once(load(path.as_deref()), format).await,
+        Command::Watch { interval } => watch(load(path.as_deref()), interval).await,
+        Command::CheckConfig => check_config(load(path.as_deref()), path),
+        Command::Version => println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+    }
+}
+
+/// Loads the config, exiting if it is invalid.
+fn load(path: Option<&Path>) -> Config {
+    Config::load(path).unwrap_or_else(|e| exit(e))
+}
+
+async fn serve(config: Config) {
+    let shared = stat_monitor::start(config).unwrap_or_else(|e| exit(e));
+
+    let addr = shared.config().addr();
+    if let Some(root) = &shared.config().replay {
+        log::info!("Replaying the capture in {}", root.display());
+    }
     log::info!("Listening on {}", addr);
 
+    let app = stat_monitor::router(shared);
     axum::Server::bind(&addr)
         .serve(app.into_make_service())
         .await
         .unwrap();
 }
 
-async fn root(State(state): State<Arc<Mutex<AppState>>>) -> Json<serde_json::Value> {
-    let mut state = state.lock().await;
-    let now = chrono::Utc::now().timestamp();
-    if now - state.last_updated > 5 {
-        let sys = System::new();
-        let mem = match sys.memory() {
-            Ok(mem) => Some(Memory {
-                used: saturating_sub_bytes(mem.total, mem.free).as_u64(),
-                total: mem.total.as_u64(),
-            }),
-            Err(_) => None
-        };
-        let swap = match sys.swap() {
-            Ok(swap) => Some(Memory {
-                used: saturating_sub_bytes(swap.total, swap.free).as_u64(),
-                total: swap.total.as_u64(),
-            }),
-            Err(_) => None
-        };
-        let cpu = sys.cpu_load_aggregate();
-        sleep(Duration::from_secs(1)).await;
-        let cpu_usage = cpu.and_then(|f| f.done());
-
-        if mem.is_none() {
-            return serde_json::json!({ "error": "failed to get memory usage" }).into();
-        }
-        if swap.is_none() {
-            return serde_json::json!({ "error": "failed to get swap usage" }).into();
-        }
-        if cpu_usage.is_err() {
-            return serde_json::json!({ "error": "failed to get cpu usage" }).into();
+async fn once(config: Config, format: Format) {
+    let snapshot = sampler(config).sample().await;
+    match format {
+        Format::Json => {
+            let json = stat_monitor::snapshot_json(&snapshot);
+            println!("{}", serde_json::to_string_pretty(&json).unwrap());
         }
+        Format::Table => print!("{}", stat_monitor::table::render(&snapshot)),
+    }
+    if snapshot.core_failed() {
+        process::exit(1);
+    }
+}
+
+/// Redraws the table every `seconds`. Rates appear from the second sample
+/// on, once there is a previous one to compare with.
+async fn watch(config: Config, seconds: Option<u64>) {
+    let period = seconds.map_or(config.sample_interval(), Duration::from_secs);
+    let mut sampler = sampler(config);
+    let mut ticker = interval(period);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        let snapshot = sampler.sample().await;
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
+        let mut stdout = io::stdout().lock();
+        // Clear the screen and move the cursor to the top left first.
+        let _ = write!(stdout, "\x1b[2J\x1b[H{}\n\n{}", now, stat_monitor::table::render(&snapshot));
+        let _ = stdout.flush();
+    }
+}
 
-        let cpu_usage = cpu_usage.unwrap();
-        let swap_usage = swap.unwrap();
-        let mem_usage = mem.unwrap();
-
-        state.cpu_usage = CPU {
-            user: cpu_usage.user * 100.0,
-            nice: cpu_usage.nice * 100.0,
-            interrupt: cpu_usage.interrupt * 100.0,
-            system: cpu_usage.system * 100.0,
-            idle: cpu_usage.idle * 100.0,
-        };
-        state.memory_usage = Memory {
-            used: mem_usage.used,
-            total: mem_usage.total,
-        };
-        state.swap_usage = Memory {
-            used: swap_usage.used,
-            total: swap_usage.total,
-        };
-        state.last_updated = now;
-        return serde_json::json!({
-            "cpu": state.cpu_usage,
-            "memory": state.memory_usage,
-            "swap": state.swap_usage,
-        }).into();
-    } else {
-        return serde_json::json!({
-            "cpu": state.cpu_usage,
-            "memory": state.memory_usage,
-            "swap": state.swap_usage,
-        }).into();
+fn check_config(config: Config, path: Option<PathBuf>) {
+    let registry = Registry::builtin().configure(&config.collectors).unwrap_or_else(|e| exit(e));
+    match path {
+        Some(path) => println!("{}: OK", path.display()),
+        None => println!("No config file, using the defaults: OK"),
     }
+    println!("Listening on: {}", config.addr());
+    let collectors: Vec<&str> = registry.info().iter().map(|c| c.name).collect();
+    println!("Collectors: {}", collectors.join(", "));
+    println!("Alert rules: {}", config.alerts.rules.len());
+    if let Some(root) = &config.replay {
+        println!("Replaying: {}", root.display());
+    }
+}
+
+/// A sampler of the collectors enabled in `config`.
+fn sampler(config: Config) -> Sampler {
+    let registry = Registry::builtin().configure(&config.collectors).unwrap_or_else(|e| exit(e));
+    Sampler::new(Arc::new(config), registry)
+}
+
+/// Reports a config error and exits with status 1.
+fn exit(e: impl std::fmt::Display) -> ! {
+    log::error!("{}", e);
+    process::exit(1);
 }
@@ -0,0 +1,52 @@
+use std::cmp::Ordering;
+
+use axum::extract::State;
+use axum::Json;
+use serde::Deserialize;
+
+use crate::collector::processes::Process;
+use crate::error::ApiError;
+use crate::extract::ApiQuery;
+use crate::Shared;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortBy {
+    #[default]
+    Cpu,
+    Rss,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProcessesQuery {
+    /// Number of processes to return, 10 by default.
+    limit: Option<usize>,
+    #[serde(default)]
+    sort: SortBy,
+}
+
+/// Lists the top processes of the latest sample by CPU usage or resident
+/// memory.
+pub async fn processes(
+    State(shared): State<Shared>,
+    ApiQuery(query): ApiQuery<ProcessesQuery>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let state = shared.state.read().await;
+    shared.check_fresh(&state)?;
+    let Some(processes) = &state.processes else {
+        let error = state.errors.iter().find(|e| e.collector == "processes").cloned();
+        return Err(error.map_or(ApiError::NotReady, ApiError::CollectorFailed));
+    };
+
+    let mut top: Vec<&Process> = processes.iter().collect();
+    top.sort_by(|a, b| match query.sort {
+        SortBy::Cpu => b.cpu.partial_cmp(&a.cpu).unwrap_or(Ordering::Equal),
+        SortBy::Rss => b.rss.cmp(&a.rss),
+    });
+    top.truncate(query.limit.unwrap_or(10));
+
+    Ok(serde_json::json!({
+        "processes": top,
+        "last_updated": state.last_updated,
+    }).into())
+}
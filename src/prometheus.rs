@@ -0,0 +1,273 @@
+use std::fmt::{Display, Write};
+
+use crate::collector::cgroups::Cgroup;
+use crate::collector::diskio::{Device, IoCounters};
+use crate::collector::disks::Disk;
+use crate::collector::network::{Counters, Interface};
+use crate::{AppState, CPU};
+
+/// Content type of the Prometheus text exposition format.
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Renders a snapshot in the Prometheus text exposition format.
+pub fn render(state: &AppState) -> String {
+    let mut out = String::new();
+
+    if let Some(memory) = &state.memory_usage {
+        gauge(&mut out, "statmonitor_memory_used_bytes", "Memory in use, total minus available, in bytes.");
+        sample(&mut out, "statmonitor_memory_used_bytes", &[], memory.used);
+        gauge(&mut out, "statmonitor_memory_total_bytes", "Total memory, in bytes.");
+        sample(&mut out, "statmonitor_memory_total_bytes", &[], memory.total);
+        gauge(&mut out, "statmonitor_memory_free_bytes", "Memory not used for anything, in bytes.");
+        sample(&mut out, "statmonitor_memory_free_bytes", &[], memory.free);
+        gauge(&mut out, "statmonitor_memory_available_bytes", "Memory available to new programs without swapping, in bytes.");
+        sample(&mut out, "statmonitor_memory_available_bytes", &[], memory.available);
+        gauge(&mut out, "statmonitor_memory_buffers_bytes", "Memory used by block device buffers, in bytes.");
+        sample(&mut out, "statmonitor_memory_buffers_bytes", &[], memory.buffers);
+        gauge(&mut out, "statmonitor_memory_cached_bytes", "Memory used by the page cache, in bytes.");
+        sample(&mut out, "statmonitor_memory_cached_bytes", &[], memory.cached);
+        gauge(&mut out, "statmonitor_memory_shared_bytes", "Memory used by tmpfs and shared memory, in bytes.");
+        sample(&mut out, "statmonitor_memory_shared_bytes", &[], memory.shared);
+        gauge(&mut out, "statmonitor_memory_slab_bytes", "Memory used by kernel slab allocations, in bytes.");
+        sample(&mut out, "statmonitor_memory_slab_bytes", &[], memory.slab);
+        gauge(&mut out, "statmonitor_memory_dirty_bytes", "Memory waiting to be written back to disk, in bytes.");
+        sample(&mut out, "statmonitor_memory_dirty_bytes", &[], memory.dirty);
+        gauge(&mut out, "statmonitor_memory_writeback_bytes", "Memory being written back to disk, in bytes.");
+        sample(&mut out, "statmonitor_memory_writeback_bytes", &[], memory.writeback);
+    }
+
+    if let Some(swap) = &state.swap_usage {
+        gauge(&mut out, "statmonitor_swap_used_bytes", "Swap in use, in bytes.");
+        sample(&mut out, "statmonitor_swap_used_bytes", &[], swap.used);
+        gauge(&mut out, "statmonitor_swap_total_bytes", "Total swap, in bytes.");
+        sample(&mut out, "statmonitor_swap_total_bytes", &[], swap.total);
+    }
+
+    if let Some(pressure) = &state.pressure {
+        let resources = [("cpu", &pressure.cpu), ("memory", &pressure.memory), ("io", &pressure.io)];
+        let stalls = || {
+            resources.iter().flat_map(|(resource, r)| {
+                [("some", Some(&r.some)), ("full", r.full.as_ref())]
+                    .into_iter()
+                    .filter_map(move |(kind, stall)| Some((*resource, kind, stall?)))
+            })
+        };
+        gauge(&mut out, "statmonitor_pressure_percent", "Share of time tasks were stalled on a resource, averaged over a window, in percent.");
+        for (resource, kind, stall) in stalls() {
+            for (window, value) in [("10", stall.avg10), ("60", stall.avg60), ("300", stall.avg300)] {
+                sample(&mut out, "statmonitor_pressure_percent", &[("resource", resource), ("kind", kind), ("window", window)], value);
+            }
+        }
+        counter(&mut out, "statmonitor_pressure_stalled_seconds_total", "Time tasks were stalled on a resource, in seconds.");
+        for (resource, kind, stall) in stalls() {
+            let seconds = stall.total as f64 / 1_000_000.0;
+            sample(&mut out, "statmonitor_pressure_stalled_seconds_total", &[("resource", resource), ("kind", kind)], seconds);
+        }
+    }
+
+    if let Some(cpu) = &state.cpu_usage {
+        gauge(&mut out, "statmonitor_cpu_percent", "Aggregate CPU time spent in each mode, in percent.");
+        for (mode, value) in modes(cpu) {
+            sample(&mut out, "statmonitor_cpu_percent", &[("mode", mode)], value);
+        }
+
+        gauge(&mut out, "statmonitor_cpu_core_percent", "Per-core CPU time spent in each mode, in percent.");
+        for (i, core) in state.cpu_cores.iter().enumerate() {
+            let core_label = i.to_string();
+            for (mode, value) in modes(core) {
+                sample(&mut out, "statmonitor_cpu_core_percent", &[("core", &core_label), ("mode", mode)], value);
+            }
+        }
+    }
+
+    if let Some(load) = &state.load_average {
+        gauge(&mut out, "statmonitor_load1", "1-minute load average.");
+        sample(&mut out, "statmonitor_load1", &[], load.one);
+        gauge(&mut out, "statmonitor_load5", "5-minute load average.");
+        sample(&mut out, "statmonitor_load5", &[], load.five);
+        gauge(&mut out, "statmonitor_load15", "15-minute load average.");
+        sample(&mut out, "statmonitor_load15", &[], load.fifteen);
+    }
+
+    if let Some(uptime) = state.uptime {
+        gauge(&mut out, "statmonitor_uptime_seconds", "Seconds since the system booted.");
+        sample(&mut out, "statmonitor_uptime_seconds", &[], uptime);
+    }
+    if let Some(boot_time) = state.boot_time {
+        gauge(&mut out, "statmonitor_boot_time_seconds", "Unix time the system booted at.");
+        sample(&mut out, "statmonitor_boot_time_seconds", &[], boot_time);
+    }
+
+    if let Some(cgroups) = &state.cgroups {
+        cgroup_metric(&mut out, cgroups, "statmonitor_cgroup_cpu_usage_seconds_total", "CPU time used by the cgroup, in seconds.", counter, |c| Some(c.cpu?.usage as f64 / 1_000_000.0));
+        cgroup_metric(&mut out, cgroups, "statmonitor_cgroup_memory_current_bytes", "Memory used by the cgroup, in bytes.", gauge, |c| Some(c.memory?.current as f64));
+        cgroup_metric(&mut out, cgroups, "statmonitor_cgroup_memory_max_bytes", "Memory limit of the cgroup, in bytes.", gauge, |c| Some(c.memory?.max? as f64));
+        cgroup_metric(&mut out, cgroups, "statmonitor_cgroup_io_read_bytes_total", "Bytes read by the cgroup.", counter, |c| Some(c.io?.read_bytes as f64));
+        cgroup_metric(&mut out, cgroups, "statmonitor_cgroup_io_written_bytes_total", "Bytes written by the cgroup.", counter, |c| Some(c.io?.written_bytes as f64));
+        cgroup_metric(&mut out, cgroups, "statmonitor_cgroup_io_reads_total", "Reads by the cgroup.", counter, |c| Some(c.io?.reads as f64));
+        cgroup_metric(&mut out, cgroups, "statmonitor_cgroup_io_writes_total", "Writes by the cgroup.", counter, |c| Some(c.io?.writes as f64));
+        cgroup_metric(&mut out, cgroups, "statmonitor_cgroup_pids", "Processes in the cgroup.", gauge, |c| Some(c.pids?.current as f64));
+        cgroup_metric(&mut out, cgroups, "statmonitor_cgroup_pids_max", "Process limit of the cgroup.", gauge, |c| Some(c.pids?.max? as f64));
+    }
+
+    if let Some(thermal) = &state.thermal {
+        if let Some(cpu) = thermal.cpu {
+            gauge(&mut out, "statmonitor_cpu_temperature_celsius", "CPU package temperature, in degrees Celsius.");
+            sample(&mut out, "statmonitor_cpu_temperature_celsius", &[], cpu);
+        }
+        if !thermal.zones.is_empty() {
+            gauge(&mut out, "statmonitor_thermal_zone_celsius", "Temperature of a thermal zone, in degrees Celsius.");
+            for zone in &thermal.zones {
+                let labels = [("zone", zone.name.as_str()), ("type", zone.kind.as_str())];
+                sample(&mut out, "statmonitor_thermal_zone_celsius", &labels, zone.temperature);
+            }
+        }
+        if !thermal.sensors.is_empty() {
+            gauge(&mut out, "statmonitor_hwmon_temperature_celsius", "Temperature of a hardware monitoring sensor, in degrees Celsius.");
+            for sensor in &thermal.sensors {
+                let labels = [
+                    ("device", sensor.device.as_str()),
+                    ("chip", sensor.chip.as_str()),
+                    ("sensor", sensor.label.as_str()),
+                ];
+                sample(&mut out, "statmonitor_hwmon_temperature_celsius", &labels, sensor.temperature);
+            }
+        }
+    }
+
+    if let Some(disks) = &state.disks {
+        disk_gauge(&mut out, disks, "statmonitor_disk_total_bytes", "Size of the filesystem, in bytes.", |d| d.total);
+        disk_gauge(&mut out, disks, "statmonitor_disk_free_bytes", "Free space on the filesystem, in bytes.", |d| d.free);
+        disk_gauge(&mut out, disks, "statmonitor_disk_available_bytes", "Free space available to unprivileged users, in bytes.", |d| d.available);
+        disk_gauge(&mut out, disks, "statmonitor_disk_inodes_total", "Total inodes on the filesystem.", |d| d.inodes_total);
+        disk_gauge(&mut out, disks, "statmonitor_disk_inodes_free", "Free inodes on the filesystem.", |d| d.inodes_free);
+        disk_gauge(&mut out, disks, "statmonitor_disk_inodes_available", "Free inodes available to unprivileged users.", |d| d.inodes_available);
+    }
+
+    if let Some(devices) = &state.disk_io {
+        diskio_counter(&mut out, devices, "statmonitor_diskio_reads_completed_total", "Reads completed.", |c| c.reads as f64);
+        diskio_counter(&mut out, devices, "statmonitor_diskio_writes_completed_total", "Writes completed.", |c| c.writes as f64);
+        diskio_counter(&mut out, devices, "statmonitor_diskio_read_bytes_total", "Bytes read.", |c| c.read_bytes as f64);
+        diskio_counter(&mut out, devices, "statmonitor_diskio_written_bytes_total", "Bytes written.", |c| c.written_bytes as f64);
+        diskio_counter(&mut out, devices, "statmonitor_diskio_read_time_seconds_total", "Time spent on reads, summed over all reads, in seconds.", |c| c.read_time as f64 / 1000.0);
+        diskio_counter(&mut out, devices, "statmonitor_diskio_write_time_seconds_total", "Time spent on writes, summed over all writes, in seconds.", |c| c.write_time as f64 / 1000.0);
+        diskio_counter(&mut out, devices, "statmonitor_diskio_io_time_seconds_total", "Time the device had I/O in progress, in seconds.", |c| c.io_time as f64 / 1000.0);
+        gauge(&mut out, "statmonitor_diskio_in_flight", "Requests currently in progress.");
+        for device in devices {
+            sample(&mut out, "statmonitor_diskio_in_flight", &[("device", &device.name)], device.total.in_flight);
+        }
+    }
+
+    if let Some(interfaces) = &state.network {
+        network_counter(&mut out, interfaces, "statmonitor_network_receive_bytes_total", "Bytes received.", |c| c.rx_bytes);
+        network_counter(&mut out, interfaces, "statmonitor_network_transmit_bytes_total", "Bytes transmitted.", |c| c.tx_bytes);
+        network_counter(&mut out, interfaces, "statmonitor_network_receive_packets_total", "Packets received.", |c| c.rx_packets);
+        network_counter(&mut out, interfaces, "statmonitor_network_transmit_packets_total", "Packets transmitted.", |c| c.tx_packets);
+        network_counter(&mut out, interfaces, "statmonitor_network_receive_errors_total", "Receive errors.", |c| c.rx_errors);
+        network_counter(&mut out, interfaces, "statmonitor_network_transmit_errors_total", "Transmit errors.", |c| c.tx_errors);
+        network_counter(&mut out, interfaces, "statmonitor_network_receive_drops_total", "Received packets dropped.", |c| c.rx_drops);
+        network_counter(&mut out, interfaces, "statmonitor_network_transmit_drops_total", "Transmitted packets dropped.", |c| c.tx_drops);
+    }
+
+    gauge(&mut out, "statmonitor_collector_success", "Whether a collector succeeded in the last sample.");
+    for collector in &state.collectors {
+        let success = !state.errors.iter().any(|e| e.collector == *collector);
+        sample(&mut out, "statmonitor_collector_success", &[("collector", collector)], success as u8);
+    }
+
+    gauge(&mut out, "statmonitor_last_updated_timestamp_seconds", "Unix time of the last sample.");
+    sample(&mut out, "statmonitor_last_updated_timestamp_seconds", &[], state.last_updated);
+
+    out
+}
+
+fn modes(cpu: &CPU) -> [(&'static str, f32); 5] {
+    [
+        ("user", cpu.user),
+        ("nice", cpu.nice),
+        ("system", cpu.system),
+        ("interrupt", cpu.interrupt),
+        ("idle", cpu.idle),
+    ]
+}
+
+fn disk_gauge(out: &mut String, disks: &[Disk], name: &str, help: &str, value: fn(&Disk) -> u64) {
+    gauge(out, name, help);
+    for disk in disks {
+        let labels = [("mount", disk.mount.as_str()), ("device", &disk.device), ("fs_type", &disk.fs_type)];
+        sample(out, name, &labels, value(disk));
+    }
+}
+
+fn diskio_counter(out: &mut String, devices: &[Device], name: &str, help: &str, value: fn(&IoCounters) -> f64) {
+    counter(out, name, help);
+    for device in devices {
+        sample(out, name, &[("device", &device.name)], value(&device.total));
+    }
+}
+
+/// Writes a metric family for the cgroups that have a `value`, if any do.
+fn cgroup_metric(
+    out: &mut String,
+    cgroups: &[Cgroup],
+    name: &str,
+    help: &str,
+    kind: fn(&mut String, &str, &str),
+    value: fn(&Cgroup) -> Option<f64>,
+) {
+    let mut values = cgroups.iter().filter_map(|c| Some((c, value(c)?))).peekable();
+    if values.peek().is_none() {
+        return;
+    }
+    kind(out, name, help);
+    for (cgroup, value) in values {
+        sample(out, name, &[("cgroup", &cgroup.path)], value);
+    }
+}
+
+fn network_counter(out: &mut String, interfaces: &[Interface], name: &str, help: &str, value: fn(&Counters<u64>) -> u64) {
+    counter(out, name, help);
+    for interface in interfaces {
+        sample(out, name, &[("interface", &interface.name)], value(&interface.total));
+    }
+}
+
+/// Writes the `HELP` and `TYPE` lines for a gauge.
+fn gauge(out: &mut String, name: &str, help: &str) {
+    describe(out, name, help, "gauge");
+}
+
+/// Writes the `HELP` and `TYPE` lines for a counter.
+fn counter(out: &mut String, name: &str, help: &str) {
+    describe(out, name, help, "counter");
+}
+
+fn describe(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Writes a single sample line, escaping label values as the format requires.
+fn sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: impl Display) {
+    out.push_str(name);
+    if !labels.is_empty() {
+        out.push('{');
+        for (i, (key, value)) in labels.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(out, "{}=\"", key);
+            for c in value.chars() {
+                match c {
+                    '\\' => out.push_str("\\\\"),
+                    '"' => out.push_str("\\\""),
+                    '\n' => out.push_str("\\n"),
+                    c => out.push(c),
+                }
+            }
+            out.push('"');
+        }
+        out.push('}');
+    }
+    let _ = writeln!(out, " {}", value);
+}
@@ -0,0 +1,275 @@
+use std::fmt::Write;
+
+use crate::collector::processes::Process;
+use crate::{AppState, CPU};
+
+use Align::{Left, Right};
+
+/// Processes listed, by CPU usage.
+const TOP_PROCESSES: usize = 5;
+
+/// Characters of a command line shown before it is cut off.
+const COMMAND_WIDTH: usize = 80;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Align {
+    Left,
+    Right,
+}
+
+/// Renders a snapshot as plain-text tables for a terminal, leaving out the
+/// metrics of disabled and failed collectors.
+pub fn render(state: &AppState) -> String {
+    let mut out = String::new();
+
+    if let Some(cpu) = &state.cpu_usage {
+        line(&mut out, "CPU", &cpu_modes(cpu));
+    }
+    if !state.cpu_cores.is_empty() {
+        let cores: Vec<String> = state
+            .cpu_cores
+            .iter()
+            .enumerate()
+            .map(|(i, core)| format!("{}: {:.1}%", i, busy(core)))
+            .collect();
+        line(&mut out, "Cores", &cores.join("  "));
+    }
+    if let Some(memory) = &state.memory_usage {
+        let value = format!(
+            "{} / {} ({:.1}%), {} available",
+            bytes(memory.used),
+            bytes(memory.total),
+            percent(memory.used, memory.total),
+            bytes(memory.available)
+        );
+        line(&mut out, "Memory", &value);
+    }
+    if let Some(swap) = &state.swap_usage {
+        let value = format!("{} / {} ({:.1}%)", bytes(swap.used), bytes(swap.total), percent(swap.used, swap.total));
+        line(&mut out, "Swap", &value);
+    }
+    if let Some(load) = &state.load_average {
+        line(&mut out, "Load", &format!("{:.2} {:.2} {:.2}", load.one, load.five, load.fifteen));
+    }
+    if let Some(uptime) = state.uptime {
+        line(&mut out, "Uptime", &duration(uptime));
+    }
+    if let Some(pressure) = &state.pressure {
+        let value = format!(
+            "cpu {:.2}%  memory {:.2}%  io {:.2}% (some, avg10)",
+            pressure.cpu.some.avg10, pressure.memory.some.avg10, pressure.io.some.avg10
+        );
+        line(&mut out, "Pressure", &value);
+    }
+    if let Some(temperature) = state.thermal.as_ref().and_then(|t| t.cpu) {
+        line(&mut out, "CPU temp", &format!("{:.1} °C", temperature));
+    }
+
+    if let Some(disks) = state.disks.as_ref().filter(|d| !d.is_empty()) {
+        let rows = disks
+            .iter()
+            .map(|disk| {
+                let used = disk.total.saturating_sub(disk.free);
+                vec![
+                    disk.mount.clone(),
+                    disk.fs_type.clone(),
+                    bytes(used),
+                    bytes(disk.total),
+                    format!("{:.1}%", percent(used, disk.total)),
+                ]
+            })
+            .collect();
+        table(&mut out, &[("Mount", Left), ("Type", Left), ("Used", Right), ("Total", Right), ("Use%", Right)], rows);
+    }
+    if let Some(devices) = state.disk_io.as_ref().filter(|d| !d.is_empty()) {
+        let rows = devices
+            .iter()
+            .map(|device| match &device.rate {
+                Some(rate) => vec![
+                    device.name.clone(),
+                    format!("{}/s", bytes(rate.read_throughput as u64)),
+                    format!("{}/s", bytes(rate.write_throughput as u64)),
+                    format!("{:.1}%", rate.utilization),
+                ],
+                None => vec![device.name.clone(), "-".into(), "-".into(), "-".into()],
+            })
+            .collect();
+        table(&mut out, &[("Device", Left), ("Read", Right), ("Write", Right), ("Util", Right)], rows);
+    }
+    if let Some(interfaces) = state.network.as_ref().filter(|i| !i.is_empty()) {
+        let rows = interfaces
+            .iter()
+            .map(|interface| {
+                let (rx, tx) = match &interface.rate {
+                    Some(rate) => (
+                        format!("{}/s", bytes(rate.rx_bytes as u64)),
+                        format!("{}/s", bytes(rate.tx_bytes as u64)),
+                    ),
+                    None => ("-".into(), "-".into()),
+                };
+                vec![
+                    interface.name.clone(),
+                    rx,
+                    tx,
+                    bytes(interface.total.rx_bytes),
+                    bytes(interface.total.tx_bytes),
+                ]
+            })
+            .collect();
+        let columns = [("Interface", Left), ("RX", Right), ("TX", Right), ("RX total", Right), ("TX total", Right)];
+        table(&mut out, &columns, rows);
+    }
+    if let Some(cgroups) = state.cgroups.as_ref().filter(|c| !c.is_empty()) {
+        let rows = cgroups
+            .iter()
+            .map(|cgroup| {
+                vec![
+                    cgroup.path.clone(),
+                    cgroup.cpu.as_ref().and_then(|c| c.percent).map_or("-".into(), |p| format!("{:.1}%", p)),
+                    cgroup.memory.as_ref().map_or("-".into(), |m| bytes(m.current)),
+                    cgroup.pids.as_ref().map_or("-".into(), |p| p.current.to_string()),
+                ]
+            })
+            .collect();
+        table(&mut out, &[("Cgroup", Left), ("CPU", Right), ("Memory", Right), ("Pids", Right)], rows);
+    }
+    if let Some(thermal) = state.thermal.as_ref() {
+        let zones = thermal.zones.iter().map(|zone| (zone.kind.clone(), zone.temperature));
+        let sensors = thermal
+            .sensors
+            .iter()
+            .map(|sensor| (format!("{} {}", sensor.chip, sensor.label), sensor.temperature));
+        let rows: Vec<Vec<String>> = zones
+            .chain(sensors)
+            .map(|(name, temperature)| vec![name, format!("{:.1} °C", temperature)])
+            .collect();
+        if !rows.is_empty() {
+            table(&mut out, &[("Sensor", Left), ("Temp", Right)], rows);
+        }
+    }
+    if let Some(processes) = state.processes.as_ref().filter(|p| !p.is_empty()) {
+        let mut top: Vec<&Process> = processes.iter().collect();
+        top.sort_by(|a, b| b.cpu.total_cmp(&a.cpu));
+        let rows = top
+            .iter()
+            .take(TOP_PROCESSES)
+            .map(|process| {
+                vec![
+                    process.pid.to_string(),
+                    process.user.clone(),
+                    format!("{:.1}%", process.cpu),
+                    bytes(process.rss),
+                    command(&process.command),
+                ]
+            })
+            .collect();
+        let columns = [("PID", Right), ("User", Left), ("CPU", Right), ("RSS", Right), ("Command", Left)];
+        table(&mut out, &columns, rows);
+    }
+
+    if !state.errors.is_empty() {
+        out.push_str("\nErrors\n");
+        for error in &state.errors {
+            let _ = writeln!(out, "  {}: {}", error.collector, error.message);
+        }
+    }
+    out
+}
+
+fn line(out: &mut String, label: &str, value: &str) {
+    let _ = writeln!(out, "{:<10}{}", label, value);
+}
+
+/// Writes a table after a blank line, padding each column to its widest
+/// cell.
+fn table(out: &mut String, columns: &[(&str, Align)], rows: Vec<Vec<String>>) {
+    let mut widths: Vec<usize> = columns.iter().map(|(header, _)| header.chars().count()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let write_row = |out: &mut String, cells: &[&str]| {
+        let mut row = String::new();
+        for (i, ((cell, width), (_, align))) in cells.iter().zip(&widths).zip(columns).enumerate() {
+            if i > 0 {
+                row.push_str("  ");
+            }
+            match align {
+                Left => {
+                    let _ = write!(row, "{:<width$}", cell, width = width);
+                }
+                Right => {
+                    let _ = write!(row, "{:>width$}", cell, width = width);
+                }
+            }
+        }
+        let _ = writeln!(out, "{}", row.trim_end());
+    };
+
+    out.push('\n');
+    let headers: Vec<&str> = columns.iter().map(|(header, _)| *header).collect();
+    write_row(out, &headers);
+    for row in &rows {
+        let cells: Vec<&str> = row.iter().map(String::as_str).collect();
+        write_row(out, &cells);
+    }
+}
+
+/// A command line on a single line of at most `COMMAND_WIDTH` characters.
+fn command(command: &str) -> String {
+    let command: String = command.chars().map(|c| if c.is_control() { ' ' } else { c }).collect();
+    if command.chars().count() <= COMMAND_WIDTH {
+        return command;
+    }
+    let mut cut: String = command.chars().take(COMMAND_WIDTH - 1).collect();
+    cut.push('…');
+    cut
+}
+
+fn cpu_modes(cpu: &CPU) -> String {
+    format!(
+        "user {:.1}%  system {:.1}%  nice {:.1}%  interrupt {:.1}%  idle {:.1}%",
+        cpu.user, cpu.system, cpu.nice, cpu.interrupt, cpu.idle
+    )
+}
+
+fn busy(cpu: &CPU) -> f32 {
+    100.0 - cpu.idle
+}
+
+fn percent(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 / total as f64 * 100.0
+    }
+}
+
+/// Formats a byte count in binary units, e.g. `1.5 GiB`.
+fn bytes(value: u64) -> String {
+    const UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+    let mut value = value as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", value)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Formats seconds as days, hours and minutes, e.g. `3d 4h 5m`.
+fn duration(seconds: u64) -> String {
+    let (days, hours, minutes) = (seconds / 86_400, seconds % 86_400 / 3600, seconds % 3600 / 60);
+    if days > 0 {
+        format!("{}d {}h {}m", days, hours, minutes)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else {
+        format!("{}m", minutes)
+    }
+}
@@ -0,0 +1,81 @@
+use std::collections::BTreeSet;
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::response::Response;
+use serde::Deserialize;
+
+use crate::events::{filter, is_metric};
+use crate::Shared;
+
+/// A message sent by the client.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+enum Request {
+    Subscribe { metrics: Vec<String> },
+    Unsubscribe { metrics: Vec<String> },
+}
+
+/// Upgrades to a WebSocket on which the client subscribes to metrics with
+/// `{"action": "subscribe", "metrics": ["cpu", "memory"]}` and unsubscribes
+/// with `"action": "unsubscribe"`. Each new snapshot is then sent as
+/// `{"type": "snapshot", "data": {...}}` limited to the subscribed metrics.
+pub async fn ws(ws: WebSocketUpgrade, State(shared): State<Shared>) -> Response {
+    ws.on_upgrade(move |socket| session(socket, shared))
+}
+
+async fn session(mut socket: WebSocket, shared: Shared) {
+    let mut updates = shared.updates.clone();
+    let mut subscribed = BTreeSet::new();
+    loop {
+        let reply = tokio::select! {
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(text))) => handle(&shared, &text, &mut subscribed),
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => continue,
+            },
+            changed = updates.changed() => {
+                // The collector has stopped when the sender is dropped.
+                if changed.is_err() {
+                    return;
+                }
+                if subscribed.is_empty() {
+                    continue;
+                }
+                let state = updates.borrow_and_update().clone();
+                let metrics: Vec<String> = subscribed.iter().cloned().collect();
+                serde_json::json!({ "type": "snapshot", "data": filter(&state, Some(&metrics)) })
+            }
+        };
+        // Snapshots that arrive while this send is pending are coalesced by
+        // the watch channel, so a slow client never holds up the collector.
+        if socket.send(Message::Text(reply.to_string())).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Applies a client request and returns the acknowledgement to send back.
+fn handle(shared: &Shared, text: &str, subscribed: &mut BTreeSet<String>) -> serde_json::Value {
+    let request = match serde_json::from_str::<Request>(text) {
+        Ok(request) => request,
+        Err(e) => return serde_json::json!({ "type": "error", "message": e.to_string() }),
+    };
+    match request {
+        Request::Subscribe { metrics } => {
+            if let Some(unknown) = metrics.iter().find(|m| !is_metric(&shared.collectors, m)) {
+                return serde_json::json!({
+                    "type": "error",
+                    "message": format!("unknown metric {:?}", unknown),
+                });
+            }
+            subscribed.extend(metrics);
+        }
+        Request::Unsubscribe { metrics } => {
+            for metric in &metrics {
+                subscribed.remove(metric);
+            }
+        }
+    }
+    serde_json::json!({ "type": "subscribed", "metrics": subscribed })
+}
@@ -0,0 +1,147 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::{env, fs};
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+}
+
+/// Runs the binary on the capture in `tests/fixtures/replay`.
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_stat_monitor"))
+        .args(args)
+        .env("REPLAY_ROOT", fixture("replay"))
+        .env_remove("STATMONITOR_CONFIG")
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn once_prints_table() {
+    let table = stdout(&run(&["once", "--format", "table"]));
+    let path = fixture("replay.txt");
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, &table).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap();
+    assert_eq!(table, expected, "rerun with UPDATE_GOLDEN=1 to update {}", path.display());
+}
+
+#[test]
+fn once_prints_json() {
+    let json: serde_json::Value = serde_json::from_str(&stdout(&run(&["once"]))).unwrap();
+    assert_eq!(json["memory"]["total"], 8_009_504u64 * 1024);
+    assert_eq!(json["boot_time"], 1_700_000_000);
+    assert_eq!(json["errors"], serde_json::json!([]));
+}
+
+#[test]
+fn check_config_lists_collectors() {
+    let out = stdout(&run(&["check-config", "--config", "config.example.toml"]));
+    assert!(out.starts_with("config.example.toml: OK\n"), "{}", out);
+    assert!(out.contains("Collectors: memory, swap, disks, diskio, network, load, uptime, pressure, thermal, cpu, processes\n"));
+}
+
+/// Runs `check-config` on a config file containing `config`, expecting it to
+/// fail, and returns the error.
+fn check_config_error(name: &str, config: &str) -> String {
+    let dir = env::temp_dir().join(format!("statmonitor-cli-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("config.toml");
+    fs::write(&path, config).unwrap();
+
+    let output = run(&["check-config", "-c", path.to_str().unwrap()]);
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn check_config_rejects_unknown_collector() {
+    let error = check_config_error("collector", "[collectors]\nbogus = true\n");
+    assert!(error.contains("unknown collector \"bogus\""), "{}", error);
+}
+
+#[test]
+fn check_config_rejects_excluded_fs_type() {
+    let error = check_config_error("disks", "[disks]\ninclude_fs_types = [\"ext4\", \"tmpfs\"]\n");
+    assert!(error.contains("include_fs_types is also excluded"), "{}", error);
+}
+
+#[test]
+fn check_config_rejects_duplicate_rule_names() {
+    let rule = "[[alerts.rules]]\nname = \"m\"\nmetric = \"load.one\"\ncondition = \">\"\nthreshold = 1.0\n";
+    let error = check_config_error("rules", &rule.repeat(2));
+    assert!(error.contains("invalid alert rule \"m\": another rule has the same name"), "{}", error);
+}
+
+#[test]
+fn check_config_rejects_https_webhook() {
+    let error = check_config_error("webhook", "[alerts]\nwebhooks = [\"https://hooks.example.com/x\"]\n");
+    assert!(error.contains("only http:// URLs are supported"), "{}", error);
+}
+
+#[test]
+fn check_config_rejects_relative_cgroup_root() {
+    let error = check_config_error("cgroups", "[cgroups]\nroot = \"sys/fs/cgroup\"\n");
+    assert!(error.contains("root must be an absolute path"), "{}", error);
+}
+
+#[test]
+fn check_config_rejects_huge_sample_interval() {
+    let output = Command::new(env!("CARGO_BIN_EXE_stat_monitor"))
+        .arg("check-config")
+        .env("SAMPLE_INTERVAL", u64::MAX.to_string())
+        .env_remove("STATMONITOR_CONFIG")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("sample_interval must be between 1 second and a day"));
+}
+
+#[test]
+fn check_config_rejects_huge_history_size() {
+    let output = Command::new(env!("CARGO_BIN_EXE_stat_monitor"))
+        .arg("check-config")
+        .env("HISTORY_SIZE", "100000000000")
+        .env_remove("STATMONITOR_CONFIG")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("history_size must be at most 100000"));
+}
+
+#[test]
+fn watch_rejects_zero_interval() {
+    let output = run(&["watch", "-n", "0"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("0 is not in 1..=86400"));
+}
+
+#[test]
+fn once_fails_without_core_collectors() {
+    let dir = env::temp_dir().join(format!("statmonitor-cli-core-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_stat_monitor"))
+        .arg("once")
+        .env("REPLAY_ROOT", &dir)
+        .env_remove("STATMONITOR_CONFIG")
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["memory"], serde_json::Value::Null);
+}
+
+#[test]
+fn prints_version() {
+    let out = stdout(&run(&["version"]));
+    assert_eq!(out, format!("stat_monitor {}\n", env!("CARGO_PKG_VERSION")));
+}
@@ -0,0 +1,614 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::{env, fs};
+
+use axum::body::{Body, BoxBody};
+use axum::http::{header, Method, Request, StatusCode};
+use axum::Router;
+use futures_util::{SinkExt, StreamExt};
+use hyper::body::HttpBody;
+use tokio::sync::watch;
+use tokio::time::Duration;
+use tokio_tungstenite::tungstenite::Message;
+use tower::ServiceExt;
+
+use stat_monitor::collector::platform::{Fake, Platform};
+use stat_monitor::collector::{self, Registry, Sampler};
+use stat_monitor::config::Config;
+use stat_monitor::{router, AppState, LoadAverage, Memory, Shared, Swap, CPU};
+
+const GIB: u64 = 1 << 30;
+
+fn cpu(user: f32, system: f32) -> CPU {
+    CPU {
+        user,
+        nice: 0.0,
+        interrupt: 0.0,
+        system,
+        idle: 100.0 - user - system,
+    }
+}
+
+/// Canned values, with `/proc` and `/sys` read from the capture in
+/// `tests/fixtures/replay`.
+fn fake() -> Fake {
+    Fake {
+        root: fixture("replay"),
+        memory: Some(Memory {
+            used: 6 * GIB,
+            total: 16 * GIB,
+            free: 2 * GIB,
+            available: 10 * GIB,
+            cached: 7 * GIB,
+            ..Memory::default()
+        }),
+        swap: Some(Swap {
+            used: GIB,
+            total: 4 * GIB,
+        }),
+        cpu: Some((cpu(12.5, 2.5), vec![cpu(20.0, 5.0), cpu(5.0, 0.0)])),
+        load: Some(LoadAverage {
+            one: 0.5,
+            five: 0.25,
+            fifteen: 0.125,
+        }),
+        uptime: Some(Duration::from_secs(3600)),
+        boot_time: Some(1_700_000_000),
+        ..Fake::default()
+    }
+}
+
+/// A running API whose collector is driven by the test instead of a timer.
+struct TestServer {
+    app: Router,
+    shared: Shared,
+    publisher: watch::Sender<Arc<AppState>>,
+    sampler: Sampler,
+}
+
+impl TestServer {
+    fn new(config: &str, platform: impl Platform + 'static) -> Self {
+        TestServer::start(config, |config, registry| Sampler::with_platform(config, registry, platform))
+    }
+
+    /// Serves the capture in `tests/fixtures/replay`, as configured with
+    /// `replay`.
+    fn replay(config: &str) -> Self {
+        let config = format!("replay = {:?}\n{}", fixture("replay").display().to_string(), config);
+        TestServer::start(&config, Sampler::new)
+    }
+
+    fn start(config: &str, sampler: impl FnOnce(Arc<Config>, Registry) -> Sampler) -> Self {
+        let config: Config = toml::from_str(config).unwrap();
+        let registry = Registry::builtin().configure(&config.collectors).unwrap();
+        let (shared, publisher) = Shared::new(config, registry.info());
+        let sampler = sampler(shared.config().clone(), registry);
+        TestServer {
+            app: router(shared.clone()),
+            shared,
+            publisher,
+            sampler,
+        }
+    }
+
+    /// Takes a sample of the fake platform and publishes it.
+    async fn sample(&mut self) -> AppState {
+        let snapshot = self.sampler.sample().await;
+        self.publish(snapshot.clone()).await;
+        snapshot
+    }
+
+    async fn publish(&self, snapshot: AppState) {
+        collector::publish(&self.shared, &self.publisher, snapshot).await;
+    }
+
+    async fn get(&self, uri: &str) -> Response {
+        self.request(Method::GET, uri, "").await
+    }
+
+    /// Sends a request with a JSON `body`, if it is not empty.
+    async fn request(&self, method: Method, uri: &str, body: &str) -> Response {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = self.app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        Response {
+            status,
+            headers,
+            body: String::from_utf8(body.to_vec()).unwrap(),
+        }
+    }
+}
+
+struct Response {
+    status: StatusCode,
+    headers: axum::http::HeaderMap,
+    body: String,
+}
+
+impl Response {
+    fn json(&self) -> serde_json::Value {
+        serde_json::from_str(&self.body).unwrap()
+    }
+
+    fn header(&self, name: header::HeaderName) -> &str {
+        self.headers.get(name).unwrap().to_str().unwrap()
+    }
+}
+
+#[tokio::test]
+async fn start_rejects_invalid_config() {
+    let config = Config {
+        sample_interval: 0,
+        ..Config::default()
+    };
+    let error = stat_monitor::start(config).err().unwrap();
+    assert_eq!(error.to_string(), "invalid config: sample_interval must be between 1 second and a day");
+
+    let mut config = Config::default();
+    config.alerts.rules = toml::from_str::<Config>(LOAD_ALERT).unwrap().alerts.rules;
+    config.alerts.rules[0].metric = "bogus".into();
+    assert!(stat_monitor::start(config).is_err());
+}
+
+#[tokio::test(start_paused = true)]
+async fn not_ready_before_first_sample() {
+    let server = TestServer::new("", fake());
+
+    let response = server.get("/").await;
+    assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.json()["error"]["code"], "not_ready");
+
+    let response = server.get("/metrics").await;
+    assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test(start_paused = true)]
+async fn serves_faked_snapshot() {
+    let mut server = TestServer::new("", fake());
+    server.sample().await;
+
+    let response = server.get("/").await;
+    assert_eq!(response.status, StatusCode::OK);
+    let json = response.json();
+    assert_eq!(json["memory"]["used"], 6 * GIB);
+    assert_eq!(json["memory"]["total"], 16 * GIB);
+    assert_eq!(json["memory"]["cached"], 7 * GIB);
+    assert_eq!(json["swap"]["used"], GIB);
+    assert_eq!(json["cpu"]["user"], 12.5);
+    assert_eq!(json["cores"].as_array().unwrap().len(), 2);
+    assert_eq!(json["cores"][0]["user"], 20.0);
+    assert_eq!(json["load"]["one"], 0.5);
+    assert_eq!(json["uptime"], 3600);
+    assert_eq!(json["boot_time"], 1_700_000_000);
+    assert_eq!(json["disks"], serde_json::json!([]));
+    // Read from the fixture rather than the host.
+    assert_eq!(json["pressure"]["cpu"]["some"]["avg10"], 1.25);
+    assert_eq!(json["thermal"]["sensors"][0]["chip"], "coretemp");
+    assert_eq!(json["errors"], serde_json::json!([]));
+}
+
+#[tokio::test(start_paused = true)]
+async fn serves_prometheus_metrics() {
+    let mut server = TestServer::new("", fake());
+    server.sample().await;
+
+    let response = server.get("/metrics").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.header(header::CONTENT_TYPE), stat_monitor::prometheus::CONTENT_TYPE);
+    let lines: Vec<&str> = response.body.lines().collect();
+    assert!(lines.contains(&"statmonitor_memory_used_bytes 6442450944"));
+    assert!(lines.contains(&"statmonitor_swap_total_bytes 4294967296"));
+    assert!(lines.contains(&r#"statmonitor_cpu_percent{mode="user"} 12.5"#));
+    assert!(lines.contains(&r#"statmonitor_cpu_core_percent{core="1",mode="user"} 5"#));
+    assert!(lines.contains(&"statmonitor_load1 0.5"));
+    assert!(lines.contains(&r#"statmonitor_collector_success{collector="memory"} 1"#));
+}
+
+#[tokio::test(start_paused = true)]
+async fn cache_control_follows_cache_ttl() {
+    let mut server = TestServer::new("cache_ttl = 30\n", fake());
+    server.sample().await;
+
+    assert_eq!(server.get("/").await.header(header::CACHE_CONTROL), "max-age=30");
+    assert_eq!(server.get("/metrics").await.header(header::CACHE_CONTROL), "max-age=30");
+}
+
+#[tokio::test(start_paused = true)]
+async fn serves_latest_snapshot_until_next_sample() {
+    let mut server = TestServer::new("", fake());
+    server.sample().await;
+
+    // Requests read the published snapshot rather than sampling again.
+    let first = server.get("/").await;
+    let second = server.get("/").await;
+    assert_eq!(first.body, second.body);
+
+    let mut fake = fake();
+    fake.memory.as_mut().unwrap().used = 8 * GIB;
+    server.sampler = Sampler::with_platform(
+        server.shared.config().clone(),
+        Registry::builtin().configure(&server.shared.config().collectors).unwrap(),
+        fake,
+    );
+    server.sample().await;
+    assert_eq!(server.get("/").await.json()["memory"]["used"], 8 * GIB);
+}
+
+#[tokio::test(start_paused = true)]
+async fn stale_snapshot_is_unavailable() {
+    let mut server = TestServer::new("", fake());
+    let mut snapshot = server.sample().await;
+    // The default sampling interval is 5 seconds.
+    snapshot.last_updated -= 60;
+    server.publish(snapshot).await;
+
+    let response = server.get("/").await;
+    assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.json()["error"]["code"], "stale");
+}
+
+#[tokio::test(start_paused = true)]
+async fn failed_collector_is_reported() {
+    let mut fake = fake();
+    fake.swap = None;
+    let mut server = TestServer::new("", fake);
+    server.sample().await;
+
+    let response = server.get("/").await;
+    assert_eq!(response.status, StatusCode::INTERNAL_SERVER_ERROR);
+    let json = response.json();
+    assert_eq!(json["swap"], serde_json::Value::Null);
+    assert_eq!(json["memory"]["used"], 6 * GIB);
+    assert_eq!(json["errors"][0]["collector"], "swap");
+    assert_eq!(json["errors"][0]["code"], "unsupported");
+
+    // Prometheus still scrapes the collectors that worked.
+    let response = server.get("/metrics").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains(r#"statmonitor_collector_success{collector="swap"} 0"#));
+    assert!(response.body.contains("statmonitor_memory_used_bytes 6442450944"));
+    assert!(!response.body.contains("statmonitor_swap_used_bytes"));
+}
+
+#[tokio::test(start_paused = true)]
+async fn failed_optional_collector_is_not_an_error() {
+    let mut fake = fake();
+    fake.load = None;
+    let mut server = TestServer::new("", fake);
+    server.sample().await;
+
+    let response = server.get("/").await;
+    assert_eq!(response.status, StatusCode::OK);
+    let json = response.json();
+    assert_eq!(json["load"], serde_json::Value::Null);
+    assert_eq!(json["errors"][0]["collector"], "load");
+}
+
+#[tokio::test(start_paused = true)]
+async fn history_of_published_snapshots() {
+    let mut server = TestServer::new("", fake());
+    let snapshot = server.sampler.sample().await;
+    let now = snapshot.last_updated;
+    for age in [30, 20, 10, 0] {
+        let mut snapshot = snapshot.clone();
+        snapshot.last_updated = now - age;
+        server.publish(snapshot).await;
+    }
+
+    let json = server.get("/history").await.json();
+    assert_eq!(json["points"].as_array().unwrap().len(), 4);
+    assert_eq!(json["from"], now - 30);
+    assert_eq!(json["to"], now);
+
+    let uri = format!("/history?from={}&step=15", now - 30);
+    let points = server.get(&uri).await.json()["points"].as_array().unwrap().clone();
+    let timestamps: Vec<i64> = points.iter().map(|p| p["timestamp"].as_i64().unwrap()).collect();
+    assert_eq!(timestamps, [now - 30, now - 10]);
+    assert_eq!(points[0]["memory"]["used"], 6 * GIB);
+
+    let uri = format!("/history?step={}", i64::MAX);
+    let points = server.get(&uri).await.json()["points"].as_array().unwrap().clone();
+    assert_eq!(points.len(), 1);
+
+    let response = server.get("/history?step=0").await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(response.json()["error"]["code"], "bad_request");
+}
+
+#[tokio::test(start_paused = true)]
+async fn disabled_formats_are_not_routed() {
+    let mut server = TestServer::new("[formats]\nprometheus = false\n", fake());
+    server.sample().await;
+    assert_eq!(server.get("/metrics").await.status, StatusCode::NOT_FOUND);
+    assert_eq!(server.get("/").await.status, StatusCode::OK);
+
+    let mut server = TestServer::new("[formats]\njson = false\n", fake());
+    server.sample().await;
+    assert_eq!(server.get("/").await.status, StatusCode::NOT_FOUND);
+    assert_eq!(server.get("/history").await.status, StatusCode::NOT_FOUND);
+    assert_eq!(server.get("/metrics").await.status, StatusCode::OK);
+}
+
+#[tokio::test(start_paused = true)]
+async fn lists_enabled_collectors() {
+    let server = TestServer::new("[collectors]\nprocesses = false\n", fake());
+    let json = server.get("/collectors").await.json();
+    let names: Vec<&str> = json["collectors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|c| c["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["memory", "swap", "disks", "diskio", "network", "load", "uptime", "pressure", "thermal", "cpu"]);
+
+    // The processes collector is disabled, and so is its endpoint.
+    assert_eq!(server.get("/processes").await.status, StatusCode::NOT_FOUND);
+}
+
+/// Reads the body up to the next server-sent event and returns its data.
+async fn next_event(body: &mut BoxBody) -> serde_json::Value {
+    loop {
+        let chunk = body.data().await.unwrap().unwrap();
+        let chunk = std::str::from_utf8(&chunk).unwrap();
+        // Skips keep-alive comments.
+        if let Some(event) = chunk.strip_prefix("event:snapshot\n") {
+            let data = event.trim_end().strip_prefix("data:").unwrap();
+            return serde_json::from_str(data).unwrap();
+        }
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn streams_events() {
+    let mut server = TestServer::new("", fake());
+    let snapshot = server.sample().await;
+
+    let request = Request::get("/events?metrics=memory").body(Body::empty()).unwrap();
+    let response = server.app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "text/event-stream");
+    let mut body = response.into_body();
+
+    // The current snapshot first, then each new one.
+    let event = next_event(&mut body).await;
+    assert_eq!(event["memory"]["used"], 6 * GIB);
+    assert_eq!(event["last_updated"], snapshot.last_updated);
+    assert_eq!(event["cpu"], serde_json::Value::Null);
+
+    let mut snapshot = snapshot;
+    snapshot.last_updated += 10;
+    server.publish(snapshot.clone()).await;
+    let event = next_event(&mut body).await;
+    assert_eq!(event["last_updated"], snapshot.last_updated);
+}
+
+#[tokio::test]
+async fn streams_websocket_snapshots() {
+    let mut server = TestServer::new("", fake());
+    let listener = axum::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(server.app.clone().into_make_service());
+    let url = format!("ws://{}/ws", listener.local_addr());
+    tokio::spawn(listener);
+    let (mut socket, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+    for request in [
+        r#"{"action": "subscribe", "metrics": ["memory", "bogus"]}"#,
+        r#"{"action": "subscribe", "metrics": ["memory", "load"]}"#,
+        r#"{"action": "unsubscribe", "metrics": ["load"]}"#,
+    ] {
+        socket.send(Message::Text(request.into())).await.unwrap();
+    }
+    let mut receive = async || match socket.next().await.unwrap().unwrap() {
+        Message::Text(text) => serde_json::from_str::<serde_json::Value>(&text).unwrap(),
+        message => panic!("unexpected message {:?}", message),
+    };
+
+    let error = receive().await;
+    assert_eq!(error["type"], "error");
+    assert_eq!(error["message"], "unknown metric \"bogus\"");
+    assert_eq!(receive().await["metrics"], serde_json::json!(["load", "memory"]));
+    assert_eq!(receive().await["metrics"], serde_json::json!(["memory"]));
+
+    let snapshot = server.sample().await;
+    let message = receive().await;
+    assert_eq!(message["type"], "snapshot");
+    assert_eq!(message["data"]["memory"]["used"], 6 * GIB);
+    assert_eq!(message["data"]["last_updated"], snapshot.last_updated);
+    assert_eq!(message["data"]["load"], serde_json::Value::Null);
+}
+
+#[tokio::test(start_paused = true)]
+async fn filters_events_by_metric() {
+    let server = TestServer::new("", fake());
+    let response = server.get("/events?metrics=memory,bogus").await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+}
+
+/// An alert on the fake load of 0.5, which fires on the first sample.
+const LOAD_ALERT: &str = r#"
+[[alerts.rules]]
+name = "load"
+metric = "load.one"
+condition = ">"
+threshold = 0.1
+"#;
+
+#[tokio::test(start_paused = true)]
+async fn creates_and_lifts_silences() {
+    let server = TestServer::new(LOAD_ALERT, fake());
+
+    let body = r#"{"alert": "load", "duration": 600, "comment": "maintenance"}"#;
+    let response = server.request(Method::POST, "/alerts/silences", body).await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    let silence = response.json();
+    assert_eq!(silence["alert"], "load");
+    assert_eq!(silence["comment"], "maintenance");
+    assert_eq!(silence["ends_at"].as_i64().unwrap() - silence["starts_at"].as_i64().unwrap(), 600);
+    let id = silence["id"].as_u64().unwrap();
+
+    let json = server.get("/alerts").await.json();
+    assert_eq!(json["silences"][0]["id"], id);
+
+    let uri = format!("/alerts/silences/{}", id);
+    assert_eq!(server.request(Method::DELETE, &uri, "").await.status, StatusCode::NO_CONTENT);
+    let response = server.request(Method::DELETE, &uri, "").await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    assert_eq!(response.json()["error"]["code"], "not_found");
+    assert_eq!(server.get("/alerts").await.json()["silences"], serde_json::json!([]));
+}
+
+#[tokio::test(start_paused = true)]
+async fn rejects_invalid_silences() {
+    let server = TestServer::new(LOAD_ALERT, fake());
+    for body in [
+        r#"{"alert": "bogus", "duration": 600}"#,
+        r#"{"alert": "load", "duration": 0}"#,
+        r#"{"duration": -1}"#,
+        r#"{"duration": 9223372036854775807}"#,
+    ] {
+        let response = server.request(Method::POST, "/alerts/silences", body).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST, "{}", body);
+        assert_eq!(response.json()["error"]["code"], "bad_request");
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn malformed_requests_are_bad_requests() {
+    let mut server = TestServer::new(LOAD_ALERT, fake());
+    server.sample().await;
+    let requests = [
+        (Method::GET, "/history?from=abc", ""),
+        (Method::GET, "/processes?limit=-1", ""),
+        (Method::GET, "/events?interval=abc", ""),
+        (Method::POST, "/alerts/silences", "{"),
+        (Method::POST, "/alerts/silences", r#"{"duration": "an hour"}"#),
+    ];
+    for (method, uri, body) in requests {
+        let response = server.request(method, uri, body).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST, "{}", uri);
+        assert_eq!(response.json()["error"]["code"], "bad_request", "{}", uri);
+    }
+
+    let request = Request::post("/alerts/silences").body(Body::from(r#"{"duration": 60}"#)).unwrap();
+    let response = server.app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["error"]["code"], "bad_request");
+}
+
+#[tokio::test]
+async fn silenced_alert_is_not_notified() {
+    let (sender, mut received) = tokio::sync::mpsc::unbounded_channel();
+    let webhook = Router::new().route(
+        "/hook",
+        axum::routing::post(move |axum::Json(body): axum::Json<serde_json::Value>| async move {
+            sender.send(body).unwrap();
+        }),
+    );
+    let listener = axum::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(webhook.into_make_service());
+    let config = format!("[alerts]\nwebhooks = [\"http://{}/hook\"]\n{}", listener.local_addr(), LOAD_ALERT);
+    tokio::spawn(listener);
+    let mut server = TestServer::new(&config, fake());
+    stat_monitor::alerts::spawn(server.shared.clone());
+
+    let response = server.request(Method::POST, "/alerts/silences", r#"{"duration": 600}"#).await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    let id = response.json()["id"].as_u64().unwrap();
+    let mut snapshot = server.sample().await;
+    let alert = loop {
+        let json = server.get("/alerts").await.json();
+        if json["alerts"][0]["status"] == "firing" {
+            break json["alerts"][0].clone();
+        }
+        tokio::task::yield_now().await;
+    };
+    assert_eq!(alert["silenced"], true);
+
+    // Only the resolution after the silence is lifted is notified.
+    server.request(Method::DELETE, &format!("/alerts/silences/{}", id), "").await;
+    snapshot.load_average = Some(LoadAverage::default());
+    server.publish(snapshot).await;
+    let notification = received.recv().await.unwrap();
+    assert_eq!(notification["alert"], "load");
+    assert_eq!(notification["status"], "resolved");
+    assert_eq!(notification["value"], 0.0);
+}
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+}
+
+/// Compares `actual` with the golden file `name`, or rewrites it when
+/// `UPDATE_GOLDEN` is set.
+fn assert_golden(name: &str, actual: &str) {
+    let path = fixture(name);
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap();
+    assert!(
+        expected == actual,
+        "{} is out of date; rerun with UPDATE_GOLDEN=1 to update it\n--- expected\n{}\n--- actual\n{}",
+        path.display(),
+        expected,
+        actual
+    );
+}
+
+/// The capture reports the same output on every machine; the golden files
+/// change whenever the output formats do.
+#[tokio::test(start_paused = true)]
+async fn replay_matches_golden_output() {
+    let mut server = TestServer::replay("[collectors]\ncgroups = true\n");
+    let last_updated = server.sample().await.last_updated.to_string();
+
+    // The time of the sample is the only thing not taken from the capture.
+    let response = server.get("/").await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let mut json = response.json();
+    json["last_updated"] = 0.into();
+    assert_golden("replay.json", &(serde_json::to_string_pretty(&json).unwrap() + "\n"));
+
+    let response = server.get("/metrics").await;
+    assert_eq!(response.status, StatusCode::OK);
+    let last_updated = format!("statmonitor_last_updated_timestamp_seconds {}", last_updated);
+    let metrics = response.body.replace(&last_updated, "statmonitor_last_updated_timestamp_seconds 0");
+    assert_golden("replay.prom", &metrics);
+}
+
+#[tokio::test(start_paused = true)]
+async fn replays_processes() {
+    let mut server = TestServer::replay("");
+    server.sample().await;
+
+    let json = server.get("/processes?sort=rss&limit=2").await.json();
+    let processes = json["processes"].as_array().unwrap();
+    assert_eq!(processes[0]["command"], "/usr/local/bin/stat_monitor --config /etc/statmonitor.toml");
+    assert_eq!(processes[0]["user"], "statmon");
+    assert_eq!(processes[0]["rss"], 17204 * 1024);
+    assert_eq!(processes[1]["command"], "/sbin/init splash");
+
+    let json = server.get("/processes?limit=10").await.json();
+    let kthreadd = json["processes"].as_array().unwrap().iter().find(|p| p["pid"] == 2).unwrap();
+    assert_eq!(kthreadd["command"], "[kthreadd]");
+}
+
+#[tokio::test(start_paused = true)]
+async fn replays_container_mode() {
+    let mut server = TestServer::replay("container = true\n");
+    server.sample().await;
+
+    let json = server.get("/").await.json();
+    assert_eq!(json["errors"], serde_json::json!([]));
+    // statmonitor.service is limited to 256 MiB and half a CPU.
+    assert_eq!(json["memory"]["total"], 268_435_456);
+    assert_eq!(json["memory"]["used"], 25_165_824 - 4_194_304);
+    assert_eq!(json["cores"], serde_json::json!([]));
+}